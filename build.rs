@@ -1,16 +1,87 @@
+#[cfg(feature = "bindgen")]
 extern crate bindgen;
 
 use std::env;
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The optional XNG interfaces, each selected by the cargo feature of the same name
+#[cfg(feature = "bindgen")]
+const INTERFACES: &[&str] = &[
+    "hm", "legacy", "queuing", "sampling", "schedule", "trace", "vclock", "virq", "vtimer",
+];
 
 fn main() {
+    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
+
+    // The simulation implements the XNG API in Rust, no bindings are needed
+    if env::var_os("CARGO_FEATURE_SIM").is_some() {
+        return;
+    }
+
+    // With the bindgen feature, the bindings are always generated from the XNG headers
+    #[cfg(feature = "bindgen")]
+    generate_bindings(&out_path);
+
+    #[cfg(not(feature = "bindgen"))]
+    match pre_generated_bindings() {
+        Some(bindings) => copy_bindings(&bindings, &out_path),
+        None => panic!(
+            "No XNG bindings selected: set XNG_RELEASE to a release checked in under bindings/ or \
+             XNG_BINDINGS to a file containing the bindings, or enable the `bindgen` feature to \
+             generate them from the XNG headers"
+        ),
+    }
+}
+
+/// Get the pre-generated bindings selected via env vars, if any
+///
+/// The bindings are taken from the file given in the `XNG_BINDINGS` env var, or else from
+/// `bindings/$XNG_RELEASE.rs`.
+#[cfg(not(feature = "bindgen"))]
+fn pre_generated_bindings() -> Option<PathBuf> {
+    println!("cargo:rerun-if-env-changed=XNG_BINDINGS");
+    println!("cargo:rerun-if-env-changed=XNG_RELEASE");
+
+    match (env::var_os("XNG_BINDINGS"), env::var("XNG_RELEASE")) {
+        (Some(bindings), _) => Some(PathBuf::from(bindings)),
+        (None, Ok(release)) => Some(Path::new("bindings").join(format!("{}.rs", release))),
+        (None, Err(_)) => None,
+    }
+}
+
+/// Copy the pre-generated `bindings` to the $OUT_DIR/bindings.rs file
+#[cfg(not(feature = "bindgen"))]
+fn copy_bindings(bindings: &Path, out_path: &Path) {
+    println!("cargo:rerun-if-changed={}", bindings.display());
+
+    fs::copy(bindings, out_path.join("bindings.rs"))
+        .unwrap_or_else(|e| panic!("Couldn't copy bindings from {}: {}", bindings.display(), e));
+}
+
+/// Generate the bindings from the XNG headers to the $OUT_DIR/bindings.rs file
+///
+/// If the `XNG_RS_UPDATE_BINDINGS` env var names a release, the generated bindings are also
+/// written to `bindings/$XNG_RS_UPDATE_BINDINGS.rs`, so that they can be checked in.
+#[cfg(feature = "bindgen")]
+fn generate_bindings(out_path: &Path) {
     // Tell cargo to invalidate the built crate whenever the wrapper changes
     println!("cargo:rerun-if-changed=wrapper.h");
+    println!("cargo:rerun-if-env-changed=XNG_RS_UPDATE_BINDINGS");
 
     // The bindgen::Builder is the main entry point
     // to bindgen, and lets you build up options for
     // the resulting bindings.
-    let bindings = bindgen::Builder::default()
+    let mut builder = bindgen::Builder::default();
+
+    // Let the wrapper include the header of every enabled interface
+    for interface in INTERFACES {
+        if env::var_os(format!("CARGO_FEATURE_{}", interface.to_uppercase())).is_some() {
+            builder = builder.clang_arg(format!("-DXNG_RS_{}", interface.to_uppercase()));
+        }
+    }
+
+    let bindings = builder
         // The input header we would like to generate
         // bindings for.
         .header("wrapper.h")
@@ -25,8 +96,15 @@ fn main() {
         .expect("Unable to generate bindings");
 
     // Write the bindings to the $OUT_DIR/bindings.rs file.
-    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
     bindings
         .write_to_file(out_path.join("bindings.rs"))
         .expect("Couldn't write bindings!");
+
+    // Update the checked in bindings of a release
+    if let Ok(release) = env::var("XNG_RS_UPDATE_BINDINGS") {
+        fs::create_dir_all("bindings").expect("Couldn't create bindings/");
+        bindings
+            .write_to_file(Path::new("bindings").join(format!("{}.rs", release)))
+            .expect("Couldn't update bindings!");
+    }
 }
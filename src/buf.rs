@@ -0,0 +1,76 @@
+//! A fixed size buffer for formatted text
+
+use core::fmt;
+
+/// Replaces the end of truncated content, see [`FmtBuffer::mark_truncation`]
+const TRUNCATION_MARKER: &str = "...";
+
+/// A buffer of `N` bytes which implements `fmt::Write`
+///
+/// Everything exceeding the capacity is silently truncated, without splitting multi byte
+/// characters.
+pub(crate) struct FmtBuffer<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+    truncated: bool,
+}
+
+// not every configuration of the crate uses every method
+#[allow(dead_code)]
+impl<const N: usize> FmtBuffer<N> {
+    /// Create an empty buffer
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; N],
+            len: 0,
+            truncated: false,
+        }
+    }
+
+    /// Get the bytes written so far
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    /// Whether anything written to the buffer was truncated
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// If the content was truncated, replace its end by `...`
+    pub fn mark_truncation(&mut self) {
+        let marker = TRUNCATION_MARKER;
+        if !self.truncated || marker.len() > N {
+            return;
+        }
+        let mut len = self.len.min(N - marker.len());
+        // do not split a multi byte character
+        while len > 0 && (self.buf[len] & 0b1100_0000) == 0b1000_0000 {
+            len -= 1;
+        }
+        self.buf[len..len + marker.len()].copy_from_slice(marker.as_bytes());
+        self.len = len + marker.len();
+    }
+}
+
+impl<const N: usize> Default for FmtBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> fmt::Write for FmtBuffer<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut n = s.len().min(N - self.len);
+        if n < s.len() {
+            self.truncated = true;
+            // do not split a multi byte character
+            while !s.is_char_boundary(n) {
+                n -= 1;
+            }
+        }
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+        Ok(())
+    }
+}
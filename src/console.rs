@@ -0,0 +1,160 @@
+//! Text output via the XNG console
+//!
+//! The [`Console`] implements `core::fmt::Write`, which allows to write formatted text in `no_std`
+//! partitions. Output is buffered line by line, but one write to the hypervisor never exceeds
+//! [`MAX_WRITE_LENGTH`] bytes. The [`print!`](crate::print) and [`println!`](crate::println) macros
+//! provide a familiar interface on top of it.
+//!
+//! # Examples
+//!
+#![cfg_attr(feature = "sim", doc = "```")]
+#![cfg_attr(not(feature = "sim"), doc = "```no_run")]
+//! use core::fmt::Write;
+//! use xng_rs::console::Console;
+//!
+//! let altitude = 1337;
+//! xng_rs::println!("altitude: {}", altitude);
+//!
+//! let mut console = Console::new();
+//! writeln!(console, "altitude: {}", altitude).unwrap();
+//! ```
+
+use core::fmt;
+
+use crate::{bindings, XngError};
+
+/// The maximum number of bytes written to the console by one hypercall
+pub const MAX_WRITE_LENGTH: usize = bindings::xMaxConsoleWriteLength as usize;
+
+/// A buffered writer to the XNG console
+///
+/// The buffer is flushed on every newline, when it is full and when the writer is dropped.
+pub struct Console {
+    buf: [u8; MAX_WRITE_LENGTH],
+    len: usize,
+}
+
+impl Console {
+    /// Create a console writer with an empty buffer
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; MAX_WRITE_LENGTH],
+            len: 0,
+        }
+    }
+
+    /// Write the buffered output to the console
+    pub fn flush(&mut self) -> Result<(), XngError> {
+        if self.len == 0 {
+            return Ok(());
+        }
+        let result = write(&self.buf[..self.len]);
+        self.len = 0;
+        result
+    }
+}
+
+impl Default for Console {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Console {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+impl fmt::Write for Console {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &byte in s.as_bytes() {
+            self.buf[self.len] = byte;
+            self.len += 1;
+            if byte == b'\n' || self.len == MAX_WRITE_LENGTH {
+                self.flush().map_err(|_| fmt::Error)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Write `bytes` to the console, which must not exceed [`MAX_WRITE_LENGTH`]
+fn write(bytes: &[u8]) -> Result<(), XngError> {
+    let return_code = unsafe {
+        bindings::XWriteConsole(
+            bytes.as_ptr() as *mut _, // TODO fix to non mut pointer
+            bytes.len() as u32,       // TODO fix to usize
+        )
+    };
+    XngError::from(return_code)
+}
+
+#[doc(hidden)]
+pub fn _print(args: fmt::Arguments) {
+    // there is nobody to report a failing console to
+    let _ = fmt::write(&mut Console::new(), args);
+}
+
+/// Print to the XNG console
+///
+/// Equivalent to the [`println!`](crate::println) macro except that a newline is not printed at
+/// the end of the message.
+#[macro_export]
+macro_rules! print {
+    ($($arg:tt)*) => {
+        $crate::console::_print(format_args!($($arg)*))
+    };
+}
+
+/// Print to the XNG console, with a newline
+#[macro_export]
+macro_rules! println {
+    () => {
+        $crate::print!("\n")
+    };
+    ($($arg:tt)*) => {
+        $crate::console::_print(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
+
+#[cfg(all(test, feature = "sim"))]
+mod tests {
+    use core::fmt::Write;
+
+    use super::*;
+    use crate::sim;
+
+    #[test]
+    fn output_is_buffered_by_line() {
+        let mut console = Console::new();
+        write!(console, "altitude: ").unwrap();
+        write!(console, "{}", 1337).unwrap();
+        assert_eq!(sim::console_output(), "");
+
+        write!(console, "\nspeed: ").unwrap();
+        assert_eq!(sim::console_output(), "altitude: 1337\n");
+
+        drop(console);
+        assert_eq!(sim::console_output(), "altitude: 1337\nspeed: ");
+    }
+
+    #[test]
+    fn full_buffer_is_flushed() {
+        let mut console = Console::new();
+        let long = "a".repeat(MAX_WRITE_LENGTH + 1);
+        console.write_str(&long).unwrap();
+        assert_eq!(sim::console_output(), long[..MAX_WRITE_LENGTH]);
+
+        console.flush().unwrap();
+        assert_eq!(sim::console_output(), long);
+    }
+
+    #[test]
+    fn print_macros() {
+        crate::print!("{}", 1);
+        crate::println!(" and {}", 2);
+        crate::println!();
+        assert_eq!(sim::console_output(), "1 and 2\n\n");
+    }
+}
@@ -0,0 +1,146 @@
+//! Implementation of the `critical-section` crate for XNG partitions
+//!
+//! A critical section disables the delivery of vIRQs on the current vCPU, so that no handler
+//! preempts it. As this does not keep the other vCPUs of the partition out, they are additionally
+//! excluded by a spin lock.
+//!
+//! The id of the calling vCPU is not yet provided in SKE, so the lock can not remember its owner.
+//! Instead, a section entered with vIRQs already disabled is considered to be nested in a section
+//! of the same vCPU if the lock is taken. Otherwise it takes the lock itself. This holds as long
+//! as vIRQs are only disabled via critical sections or [`virq::free`], which takes the lock as
+//! well, and as vIRQ handlers are dispatched by the [`virq`] module, which takes the lock for
+//! them. Consequently, vIRQs must not be enabled within a critical section.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::virq;
+
+/// Whether a vCPU is in a critical section
+static LOCKED: AtomicBool = AtomicBool::new(false);
+
+/// Restore state flag: vIRQs were enabled before the critical section
+const VIRQS_ENABLED: u8 = 1 << 0;
+
+/// Restore state flag: the critical section took the lock
+const TOOK_LOCK: u8 = 1 << 1;
+
+struct XngCriticalSection;
+
+critical_section::set_impl!(XngCriticalSection);
+
+unsafe impl critical_section::Impl for XngCriticalSection {
+    unsafe fn acquire() -> critical_section::RawRestoreState {
+        // disabled before taking the lock, so that no handler of this vCPU can spin on it
+        if virq::disable_nested() {
+            lock();
+            VIRQS_ENABLED | TOOK_LOCK
+        } else if try_lock() {
+            TOOK_LOCK
+        } else {
+            0
+        }
+    }
+
+    unsafe fn release(state: critical_section::RawRestoreState) {
+        if state & TOOK_LOCK != 0 {
+            unlock();
+        }
+        if state & VIRQS_ENABLED != 0 {
+            let _ = virq::enable();
+        }
+    }
+}
+
+/// Take the lock, spinning until the vCPU owning it released it
+///
+/// vIRQs must be disabled, so that no handler preempts the owner.
+pub(crate) fn lock() {
+    while !try_lock() {
+        core::hint::spin_loop();
+    }
+}
+
+fn try_lock() -> bool {
+    LOCKED
+        .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+        .is_ok()
+}
+
+pub(crate) fn unlock() {
+    LOCKED.store(false, Ordering::Release);
+}
+
+#[cfg(all(test, feature = "sim"))]
+mod tests {
+    use core::cell::Cell;
+    use std::thread;
+
+    use critical_section::Mutex;
+
+    use super::*;
+    use crate::{partition, sim};
+
+    /// Whether the delivery of vIRQs is enabled, leaving it as it was
+    fn virqs_enabled() -> bool {
+        let enabled = virq::disable_nested();
+        if enabled {
+            virq::enable().unwrap();
+        }
+        enabled
+    }
+
+    #[test]
+    fn nested_sections_restore_the_delivery() {
+        virq::enable().unwrap();
+
+        critical_section::with(|_| {
+            assert!(!virqs_enabled());
+            critical_section::with(|_| assert!(!virqs_enabled()));
+            assert!(!virqs_enabled());
+        });
+        assert!(virqs_enabled());
+    }
+
+    #[test]
+    fn sections_exclude_each_other() {
+        static COUNTER: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+
+        // every thread acts as a vCPU of its own
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    virq::enable().unwrap();
+                    for _ in 0..250 {
+                        critical_section::with(|cs| {
+                            let counter = COUNTER.borrow(cs);
+                            let value = counter.get();
+                            thread::yield_now();
+                            counter.set(value + 1);
+                        });
+                    }
+                });
+            }
+        });
+
+        assert_eq!(critical_section::with(|cs| COUNTER.borrow(cs).get()), 1000);
+    }
+
+    #[test]
+    fn handlers_run_in_a_section() {
+        static CALLS: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+
+        virq::set_handler(20, |_| {
+            // the lock is already taken for the handler, so this section is nested
+            critical_section::with(|cs| {
+                let calls = CALLS.borrow(cs);
+                calls.set(calls.get() + 1);
+            })
+        })
+        .unwrap();
+        virq::unmask(20).unwrap();
+        virq::enable().unwrap();
+
+        sim::raise_virq(partition::my_id().unwrap(), 20);
+        assert_eq!(critical_section::with(|cs| CALLS.borrow(cs).get()), 1);
+    }
+}
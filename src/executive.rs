@@ -0,0 +1,211 @@
+//! A slot-aware cyclic executive
+//!
+//! Partitions are activated by XNG at the start of each of their schedule slots. The [`Executive`]
+//! implements the usual main loop of such a partition: on every activation it determines the
+//! current slot, runs the tasks registered for it and then yields the rest of the slot via
+//! [`wait_until_next_schedule_slot`](crate::vcpu::wait_until_next_schedule_slot).
+//!
+//! Tasks are either bound to a slot id, as defined via XCF, or to a rate, i.e. they run on every
+//! n-th activation of the partition. Tasks run in the order they were registered. The partition
+//! must run on a single vCpu, see [`VCpuId::FIRST`](crate::vcpu::VCpuId::FIRST).
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # fn main() -> Result<(), xng_rs::XngError> {
+//! use xng_rs::executive::Executive;
+//! use xng_rs::vcpu::SlotId;
+//!
+//! let mut read_sensors = |_: &_| { /* ... */ };
+//! let mut control = |_: &_| { /* ... */ };
+//! let mut housekeeping = |_: &_| { /* ... */ };
+//!
+//! let mut executive = Executive::<3>::new();
+//! executive.every(1, &mut read_sensors)?;
+//! executive.on_slot(SlotId(2), &mut control)?;
+//! executive.every(10, &mut housekeeping)?;
+//! executive.run()?;
+//! # Ok(())}
+//! ```
+
+use core::convert::Infallible;
+
+use crate::{
+    vcpu::{self, SlotId, VCpuSchedStatus},
+    XngError,
+};
+
+/// A task run by the [`Executive`]
+pub type Task<'a> = &'a mut dyn FnMut(&Activation);
+
+/// Describes the activation a task runs in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Activation {
+    /// The number of activations before this one
+    pub count: u64,
+
+    /// The slot the partition is running in
+    pub slot: VCpuSchedStatus,
+}
+
+/// When a task is run
+#[derive(Clone, Copy)]
+enum Trigger {
+    /// In every activation within the slot
+    Slot(SlotId),
+
+    /// In every n-th activation, starting with the first one
+    Every(u64),
+}
+
+impl Trigger {
+    fn matches(&self, activation: &Activation) -> bool {
+        match *self {
+            Trigger::Slot(slot_id) => activation.slot.slot_id == slot_id,
+            Trigger::Every(n) => activation.count.is_multiple_of(n),
+        }
+    }
+}
+
+/// Runs up to `N` tasks, each either in a certain slot or at a certain rate
+pub struct Executive<'a, const N: usize> {
+    tasks: [Option<(Trigger, Task<'a>)>; N],
+    activations: u64,
+}
+
+impl<'a, const N: usize> Executive<'a, N> {
+    /// Create an executive without tasks
+    pub fn new() -> Self {
+        Self {
+            tasks: [(); N].map(|_| None),
+            activations: 0,
+        }
+    }
+
+    /// Run `task` whenever the partition is activated in the slot `slot_id`
+    ///
+    /// Returns `Err(XngError::NotAvailable)` if all `N` tasks are taken.
+    pub fn on_slot(&mut self, slot_id: SlotId, task: Task<'a>) -> Result<(), XngError> {
+        self.add(Trigger::Slot(slot_id), task)
+    }
+
+    /// Run `task` on every `n`-th activation of the partition, starting with the first one
+    ///
+    /// Returns `Err(XngError::InvalidParam)` if `n` is zero and `Err(XngError::NotAvailable)` if
+    /// all `N` tasks are taken.
+    pub fn every(&mut self, n: u32, task: Task<'a>) -> Result<(), XngError> {
+        if n == 0 {
+            return Err(XngError::InvalidParam);
+        }
+        self.add(Trigger::Every(n.into()), task)
+    }
+
+    fn add(&mut self, trigger: Trigger, task: Task<'a>) -> Result<(), XngError> {
+        let free = self
+            .tasks
+            .iter_mut()
+            .find(|t| t.is_none())
+            .ok_or(XngError::NotAvailable)?;
+        *free = Some((trigger, task));
+        Ok(())
+    }
+
+    /// Run the tasks matching the current activation, without yielding the slot afterwards
+    ///
+    /// Returns the activation the tasks were run in.
+    pub fn activate(&mut self) -> Result<Activation, XngError> {
+        let activation = Activation {
+            count: self.activations,
+            slot: vcpu::current_slot()?,
+        };
+        self.activations += 1;
+
+        for (trigger, task) in self.tasks.iter_mut().flatten() {
+            if trigger.matches(&activation) {
+                task(&activation);
+            }
+        }
+        Ok(activation)
+    }
+
+    /// Run the tasks of every activation, yielding the slot in between
+    ///
+    /// Only returns if the current slot can not be determined.
+    pub fn run(mut self) -> Result<Infallible, XngError> {
+        loop {
+            self.activate()?;
+            vcpu::wait_until_next_schedule_slot();
+        }
+    }
+}
+
+impl<const N: usize> Default for Executive<'_, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(all(test, feature = "sim"))]
+mod tests {
+    use super::*;
+    use crate::{sim::Simulator, time::DurationFromInt};
+
+    #[test]
+    fn tasks_run_by_slot_and_rate() {
+        Simulator::new()
+            .partition("Control", 1, || {
+                let mut slots = Vec::new();
+                let mut every_third = Vec::new();
+                let mut on_slot = |activation: &Activation| slots.push(activation.count);
+                let mut on_rate = |activation: &Activation| every_third.push(activation.count);
+
+                let mut executive = Executive::<2>::new();
+                executive.on_slot(SlotId(2), &mut on_slot).unwrap();
+                executive.every(3, &mut on_rate).unwrap();
+                for _ in 0..6 {
+                    executive.activate().unwrap();
+                    vcpu::wait_until_next_schedule_slot();
+                }
+
+                assert_eq!(slots, [1, 3, 5]);
+                assert_eq!(every_third, [0, 3]);
+            })
+            .partition("Other", 2, || {})
+            .slot(1, 2u32.ms())
+            .slot(2, 2u32.ms())
+            .slot(1, 4u32.ms())
+            .run(3);
+    }
+
+    #[test]
+    fn activation_describes_the_slot() {
+        Simulator::new()
+            .partition("Control", 1, || {
+                let activation = Executive::<0>::new().activate().unwrap();
+                assert_eq!(activation.count, 0);
+                assert_eq!(activation.slot, vcpu::current_slot().unwrap());
+                assert_eq!(activation.slot.slot_id, SlotId(0));
+                assert_eq!(activation.slot.slot_duration, 2u32.ms());
+            })
+            .slot(1, 2u32.ms())
+            .run(1);
+    }
+
+    #[test]
+    fn tasks_are_limited() {
+        let mut never = |_: &Activation| {};
+        let mut task = |_: &Activation| {};
+        let mut other = |_: &Activation| {};
+
+        let mut executive = Executive::<1>::new();
+        assert!(matches!(
+            executive.every(0, &mut never),
+            Err(XngError::InvalidParam)
+        ));
+        executive.every(1, &mut task).unwrap();
+        assert!(matches!(
+            executive.on_slot(SlotId(0), &mut other),
+            Err(XngError::NotAvailable)
+        ));
+    }
+}
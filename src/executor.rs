@@ -0,0 +1,258 @@
+//! A minimal executor for async partition code
+//!
+//! The [`Executor`] runs a fixed number of tasks on the calling vCpu, without any allocation.
+//! Each round it polls every unfinished task. Once all of them are pending and none was woken in
+//! the meantime, it yields the remaining time of the slot to the hypervisor and polls again in the
+//! next slot.
+//!
+//! The futures of this crate, e.g. [`sleep`] or the `recv_async` methods of the ports, do not
+//! wake the executor. As XNG does not notify a partition about their progress, they are simply
+//! polled again in the next slot. Futures which do wake the executor, e.g. from a vIRQ handler,
+//! are polled again in the same slot.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # fn main() -> Result<(), xng_rs::XngError> {
+//! use core::pin::pin;
+//! use xng_rs::executor::{self, Executor};
+//! use xng_rs::prelude::*;
+//! use xng_rs::time::DurationFromInt;
+//!
+//! let telecommands = port::QueuingReceiver::<64, 8>::new(cstr!("telecommands"))?;
+//!
+//! let mut commands = pin!(async {
+//!     let mut buf = [0u8; 64];
+//!     loop {
+//!         let command = telecommands.recv_async(&mut buf).await.unwrap();
+//!         // handle command
+//!     }
+//! });
+//! let mut heartbeat = pin!(async {
+//!     loop {
+//!         executor::sleep(100u32.ms()).await.unwrap();
+//!         // send heartbeat
+//!     }
+//! });
+//!
+//! let mut executor = Executor::<2>::new();
+//! executor.spawn(commands.as_mut())?;
+//! executor.spawn(heartbeat.as_mut())?;
+//! executor.run();
+//! # Ok(())}
+//! ```
+
+use core::{
+    future::{poll_fn, Future},
+    pin::{pin, Pin},
+    sync::atomic::{AtomicBool, Ordering},
+    task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+};
+
+use crate::{
+    time::{Duration, Instant},
+    vcpu, XngError,
+};
+
+/// A task run by the [`Executor`]
+pub type Task<'a> = Pin<&'a mut dyn Future<Output = ()>>;
+
+/// Set whenever a waker of any executor is woken
+///
+/// Thus wakers do not point to a task and stay valid even if they outlive it. An executor woken
+/// by the task of another one merely polls its tasks once more.
+static WOKEN: AtomicBool = AtomicBool::new(false);
+
+/// Runs up to `N` tasks concurrently
+pub struct Executor<'a, const N: usize> {
+    tasks: [Option<Task<'a>>; N],
+}
+
+impl<'a, const N: usize> Executor<'a, N> {
+    /// Create an executor without tasks
+    pub fn new() -> Self {
+        Self {
+            tasks: [(); N].map(|_| None),
+        }
+    }
+
+    /// Add a task to be run
+    ///
+    /// Returns `Err(XngError::NotAvailable)` if all `N` tasks are taken.
+    pub fn spawn(&mut self, task: Task<'a>) -> Result<(), XngError> {
+        let free = self
+            .tasks
+            .iter_mut()
+            .find(|t| t.is_none())
+            .ok_or(XngError::NotAvailable)?;
+        *free = Some(task);
+        Ok(())
+    }
+
+    /// Run all tasks until they are finished
+    pub fn run(mut self) {
+        let waker = waker();
+        let mut cx = Context::from_waker(&waker);
+
+        loop {
+            WOKEN.store(false, Ordering::Relaxed);
+
+            for slot in self.tasks.iter_mut() {
+                if let Some(task) = slot {
+                    if task.as_mut().poll(&mut cx).is_ready() {
+                        *slot = None;
+                    }
+                }
+            }
+
+            if self.tasks.iter().all(Option::is_none) {
+                return;
+            }
+            if !WOKEN.load(Ordering::Relaxed) {
+                vcpu::wait_until_next_schedule_slot();
+            }
+        }
+    }
+}
+
+impl<const N: usize> Default for Executor<'_, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Run `future` until it is finished, yielding the slot whenever it is pending
+///
+/// # Examples
+///
+#[cfg_attr(feature = "sim", doc = "```")]
+#[cfg_attr(not(feature = "sim"), doc = "```no_run")]
+/// use xng_rs::executor;
+/// use xng_rs::time::DurationFromInt;
+///
+/// executor::block_on(executor::sleep(1u32.secs())).unwrap();
+/// ```
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = waker();
+    let mut cx = Context::from_waker(&waker);
+
+    loop {
+        WOKEN.store(false, Ordering::Relaxed);
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        if !WOKEN.load(Ordering::Relaxed) {
+            vcpu::wait_until_next_schedule_slot();
+        }
+    }
+}
+
+/// Wait until `deadline` has passed
+///
+/// The wait ends once the task is polled after `deadline`, at the latest in the first slot
+/// following it.
+pub async fn sleep_until(deadline: Instant) -> Result<(), XngError> {
+    poll_fn(|_| match Instant::now() {
+        Ok(now) if now < deadline => Poll::Pending,
+        Ok(_) => Poll::Ready(Ok(())),
+        Err(e) => Poll::Ready(Err(e)),
+    })
+    .await
+}
+
+/// Wait for at least `duration`
+pub async fn sleep(duration: Duration) -> Result<(), XngError> {
+    let deadline = Instant::now()?
+        .checked_add(duration)
+        .ok_or(crate::time::TimeError::Overflow)?;
+    sleep_until(deadline).await
+}
+
+fn waker() -> Waker {
+    const VTABLE: RawWakerVTable = RawWakerVTable::new(
+        |_| RawWaker::new(core::ptr::null(), &VTABLE),
+        |_| WOKEN.store(true, Ordering::Relaxed),
+        |_| WOKEN.store(true, Ordering::Relaxed),
+        |_| {},
+    );
+
+    // SAFETY: the vtable functions do not use the data pointer
+    unsafe { Waker::from_raw(RawWaker::new(core::ptr::null(), &VTABLE)) }
+}
+
+#[cfg(all(test, feature = "sim"))]
+mod tests {
+    use super::*;
+    use crate::{cstr, port, sim, time::DurationFromInt};
+
+    #[test]
+    fn sleep_yields_until_the_deadline() {
+        sim::set_slot_period(5u32.ms());
+        let start = Instant::now().unwrap();
+
+        block_on(sleep(12u32.ms())).unwrap();
+        assert_eq!(start.elapsed().unwrap(), 15u32.ms());
+    }
+
+    #[test]
+    fn woken_task_is_polled_in_the_same_slot() {
+        let start = Instant::now().unwrap();
+        let mut polls = 0;
+
+        block_on(poll_fn(|cx| {
+            polls += 1;
+            if polls < 3 {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            } else {
+                Poll::Ready(())
+            }
+        }));
+        assert_eq!(polls, 3);
+        assert_eq!(start.elapsed().unwrap(), Duration::ZERO);
+    }
+
+    #[test]
+    fn tasks_run_concurrently() {
+        sim::connect("commands_out", "commands_in");
+        let sender = port::QueuingSender::<1, 4>::new(cstr!("commands_out")).unwrap();
+        let receiver = port::QueuingReceiver::<1, 4>::new(cstr!("commands_in")).unwrap();
+        let mut received = [0; 3];
+
+        {
+            let mut producer = pin!(async {
+                for command in 1..=3 {
+                    sleep(1u32.ms()).await.unwrap();
+                    sender.send(&[command]).unwrap();
+                }
+            });
+            let mut consumer = pin!(async {
+                let mut buf = [0; 1];
+                for slot in received.iter_mut() {
+                    *slot = receiver.recv_async(&mut buf).await.unwrap()[0];
+                }
+            });
+
+            let mut executor = Executor::<2>::new();
+            executor.spawn(producer.as_mut()).unwrap();
+            executor.spawn(consumer.as_mut()).unwrap();
+            executor.run();
+        }
+
+        assert_eq!(received, [1, 2, 3]);
+    }
+
+    #[test]
+    fn spawn_is_limited() {
+        let mut first = pin!(async {});
+        let mut second = pin!(async {});
+
+        let mut executor = Executor::<1>::new();
+        executor.spawn(first.as_mut()).unwrap();
+        assert!(matches!(
+            executor.spawn(second.as_mut()),
+            Err(XngError::NotAvailable)
+        ));
+    }
+}
@@ -0,0 +1,160 @@
+//! Functions related to the health monitor
+//!
+//! The health monitor (HM) of XNG collects events which indicate a fault in a partition. Depending
+//! on the XCF, XNG reacts to an event e.g. by logging it, restarting or halting the partition.
+//!
+//! # Examples
+//!
+#![cfg_attr(feature = "sim", doc = "```")]
+#![cfg_attr(not(feature = "sim"), doc = "```no_run")]
+//! # fn main() -> Result<(), xng_rs::XngError> {
+//! use xng_rs::hm::{self, HmEvent};
+//!
+//! let altitude = -3;
+//! hm::report_fmt(
+//!     HmEvent::ApplicationError,
+//!     format_args!("implausible altitude {}", altitude),
+//! )?;
+//! # Ok(())}
+//! ```
+
+use core::fmt;
+
+use crate::{bindings, buf::FmtBuffer, XngError};
+
+/// The maximum length of a message reported to the health monitor
+pub const MAX_MESSAGE_LENGTH: usize = bindings::xMaxHmMessageLength as usize;
+
+/// An event which can be reported to the health monitor
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum HmEvent {
+    /// The application detected an error
+    ApplicationError = bindings::xHmApplicationError,
+
+    /// A numeric error, e.g. an overflow or a division by zero, occured
+    NumericError = bindings::xHmNumericError,
+
+    /// A deadline was missed
+    DeadlineMissed = bindings::xHmDeadlineMissed,
+
+    /// The partition issued an illegal request
+    IllegalRequest = bindings::xHmIllegalRequest,
+
+    /// The stack overflowed
+    StackOverflow = bindings::xHmStackOverflow,
+
+    /// A memory violation occured
+    MemoryViolation = bindings::xHmMemoryViolation,
+}
+
+/// Report an event to the health monitor
+///
+/// Messages longer than [`MAX_MESSAGE_LENGTH`] are truncated.
+pub fn report(event: HmEvent, message: &[u8]) -> Result<(), XngError> {
+    let len = message.len().min(MAX_MESSAGE_LENGTH);
+
+    let return_code = unsafe {
+        bindings::XReportHmEvent(
+            event as bindings::xHmEvent_t,
+            0,
+            message.as_ptr() as *mut _, // TODO fix to non mut pointer
+            len as u32,                 // TODO fix to usize
+        )
+    };
+    XngError::from(return_code)
+}
+
+/// Report an event with a formatted message to the health monitor
+///
+/// Messages longer than [`MAX_MESSAGE_LENGTH`] are truncated, their end is replaced by `...`.
+pub fn report_fmt(event: HmEvent, args: fmt::Arguments) -> Result<(), XngError> {
+    let mut message = FmtBuffer::<MAX_MESSAGE_LENGTH>::new();
+    // the buffer truncates instead of failing
+    let _ = fmt::write(&mut message, args);
+    message.mark_truncation();
+    report(event, message.as_bytes())
+}
+
+/// A buffer for health monitor messages
+///
+/// The buffer implements `fmt::Write`. Everything exceeding [`MAX_MESSAGE_LENGTH`] is silently
+/// truncated.
+#[derive(Default)]
+pub struct HmMessage(FmtBuffer<MAX_MESSAGE_LENGTH>);
+
+impl HmMessage {
+    /// Create an empty message
+    pub const fn new() -> Self {
+        Self(FmtBuffer::new())
+    }
+
+    /// Get the bytes written so far
+    pub fn as_bytes(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+
+    /// Whether anything written to the message was truncated
+    pub fn is_truncated(&self) -> bool {
+        self.0.is_truncated()
+    }
+}
+
+impl fmt::Write for HmMessage {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.write_str(s)
+    }
+}
+
+#[cfg(all(test, feature = "sim"))]
+mod tests {
+    use core::fmt::Write;
+
+    use super::*;
+    use crate::sim;
+
+    #[test]
+    fn events_are_reported() {
+        report(HmEvent::DeadlineMissed, b"too late").unwrap();
+        report_fmt(HmEvent::NumericError, format_args!("{} / 0", 1)).unwrap();
+
+        assert_eq!(
+            sim::hm_events(),
+            [
+                (0, HmEvent::DeadlineMissed, b"too late".to_vec()),
+                (0, HmEvent::NumericError, b"1 / 0".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn long_messages_are_truncated() {
+        let long = [b'a'; MAX_MESSAGE_LENGTH + 1];
+        report(HmEvent::ApplicationError, &long).unwrap();
+        report_fmt(
+            HmEvent::ApplicationError,
+            format_args!("{}", core::str::from_utf8(&long).unwrap()),
+        )
+        .unwrap();
+
+        let events = sim::hm_events();
+        assert_eq!(events[0].2, &long[..MAX_MESSAGE_LENGTH]);
+        assert_eq!(events[1].2.len(), MAX_MESSAGE_LENGTH);
+        assert!(events[1].2.ends_with(b"a..."));
+    }
+
+    #[test]
+    fn message_buffer_tracks_truncation() {
+        let mut message = HmMessage::new();
+        write!(message, "short").unwrap();
+        assert_eq!(message.as_bytes(), b"short");
+        assert!(!message.is_truncated());
+
+        for _ in 0..MAX_MESSAGE_LENGTH {
+            write!(message, "ä").unwrap();
+        }
+        assert!(message.is_truncated());
+        assert!(message.as_bytes().len() <= MAX_MESSAGE_LENGTH);
+        assert!(core::str::from_utf8(message.as_bytes()).is_ok());
+    }
+}
@@ -17,6 +17,7 @@
 
 /// This module contains the bindings to the C ABI of XNG. It is advised to never use this directly
 /// from outside of `xng-rs`.
+#[cfg(not(feature = "sim"))]
 pub mod bindings {
     #![allow(clippy::redundant_static_lifetimes)]
     #![allow(dead_code)]
@@ -27,12 +28,45 @@ pub mod bindings {
     include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 }
 
+/// This module contains the simulated bindings to the C ABI of XNG. It is advised to never use
+/// this directly from outside of `xng-rs`.
+#[cfg(feature = "sim")]
+pub mod bindings {
+    pub use crate::sim::bindings::*;
+}
+
 pub mod prelude;
 
+#[cfg(any(feature = "hm", feature = "trace"))]
+mod buf;
+
+#[cfg(feature = "legacy")]
+pub mod console;
+#[cfg(feature = "critical-section")]
+mod cs;
+#[cfg(feature = "schedule")]
+pub mod executive;
+#[cfg(feature = "schedule")]
+pub mod executor;
+#[cfg(feature = "hm")]
+pub mod hm;
+pub mod panic;
 pub mod partition;
 pub mod port;
+#[cfg(feature = "schedule")]
+pub mod schedule;
+#[cfg(feature = "sim")]
+pub mod sim;
 pub mod time;
+#[cfg(feature = "embassy-time")]
+mod time_driver;
+#[cfg(feature = "vtimer")]
+pub mod timer;
+#[cfg(feature = "trace")]
+pub mod trace;
 pub mod vcpu;
+#[cfg(feature = "virq")]
+pub mod virq;
 
 /// The XNG error type
 ///
@@ -52,6 +86,8 @@ pub enum XngError {
     InvalidMode,
     /// A function returned a return code which we do not know
     UnknownReturnCode(bindings::xReturnCode_t),
+    /// XNG reported a vCpu state which we do not know
+    UnknownVCpuState(bindings::xVCpuState_t),
     /// The buffer is too big
     BufTooBig {
         /// The size of the buffer
@@ -66,6 +102,15 @@ pub enum XngError {
         /// The maximum allowed size
         min_required: usize,
     },
+    /// A message had not the expected length
+    MessageLength {
+        /// The expected length of the message
+        expected: usize,
+        /// The length of the message actually received
+        received: usize,
+    },
+    /// A message could not be decoded
+    MalformedMessage,
     /// A time error occured
     TimeError(time::TimeError),
 }
@@ -91,9 +136,39 @@ impl XngError {
 }
 
 /// An Xng Error with trace information
+#[derive(Debug)]
 pub struct XngErrorTrace {
     error: XngError,
-    _line: u32,
+    file: &'static str,
+    line: u32,
+}
+
+impl XngErrorTrace {
+    /// Convert a `xReturnCode_t` to an `Result<(), XngErrorTrace>`, remembering where it occured
+    ///
+    /// Usually called with `file!()` and `line!()` as `file` and `line`.
+    pub fn trace(
+        return_code: bindings::xReturnCode_t,
+        file: &'static str,
+        line: u32,
+    ) -> Result<(), Self> {
+        XngError::from(return_code).map_err(|error| Self { error, file, line })
+    }
+
+    /// The error which occured
+    pub fn error(&self) -> &XngError {
+        &self.error
+    }
+
+    /// The source file in which the error occured
+    pub fn file(&self) -> &'static str {
+        self.file
+    }
+
+    /// The line in which the error occured
+    pub fn line(&self) -> u32 {
+        self.line
+    }
 }
 
 impl From<XngErrorTrace> for XngError {
@@ -104,15 +179,31 @@ impl From<XngErrorTrace> for XngError {
 
 /// Convert a `xReturnCode_t` to an `Result<(), XngError>`
 ///
-// TODO make this work with no_std
+/// With the `std` feature enabled, the file and line in which the macro was invoked are printed on
+/// an error. Use [`XngErrorTrace::trace`] to keep them in the error instead.
 #[macro_export]
 macro_rules! to_traceable_error {
     ($return_code:expr) => {{
-        println!("Error in file {}, line {}", file!(), line!());
-        XngError::from($return_code)
+        $crate::_traceable_error($return_code, file!(), line!())
     }};
 }
 
+#[doc(hidden)]
+pub fn _traceable_error(
+    return_code: bindings::xReturnCode_t,
+    file: &'static str,
+    line: u32,
+) -> Result<(), XngError> {
+    let result = XngError::from(return_code);
+    #[cfg(feature = "std")]
+    if result.is_err() {
+        std::println!("Error in file {}, line {}", file, line);
+    }
+    #[cfg(not(feature = "std"))]
+    let _ = (file, line);
+    result
+}
+
 /// Create a NULL terminated string in C representation
 ///
 /// Use this where you would write `"Some string literal"` in C. Will panic if interior NULL bytes
@@ -125,29 +216,3 @@ macro_rules! cstr {
             .expect("InteriorG NULL bytes are not allowed in cstr literals")
     }};
 }
-
-#[cfg(not(feature = "std"))]
-#[panic_handler]
-fn panic(info: &core::panic::PanicInfo) -> ! {
-    use core2::io::{Cursor, Write};
-
-    let mut buf = [0; bindings::xMaxHmMessageLength as usize]; // TODO fix to usize
-    let mut len = 0;
-
-    let mut cur = Cursor::new(&mut buf[..]);
-
-    if let Some(s) = info.payload().downcast_ref::<&str>() {
-        write!(&mut cur, "{}", s).expect("!write");
-        len = s.len().min(buf.len());
-    }
-
-    unsafe {
-        bindings::XReportHmEvent(
-            bindings::xHmApplicationError,
-            0,
-            buf.as_mut_ptr() as _,
-            len as u32, // TODO fix to usize
-        );
-    }
-    loop {}
-}
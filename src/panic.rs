@@ -1,12 +1,156 @@
-use core::fmt::{Write, self};
-use core::panic::PanicInfo;
+//! Panic handling for `no_std` partitions
+//!
+//! A panic is reported as application error to XNG's health monitor. The message contains the
+//! source location and the formatted panic message, e.g. `panicked at src/main.rs:27:4: x = 3`.
+//! If it does not fit into the health monitor message, its end is replaced by `...`. Without the
+//! `hm` feature, the message is written to the console instead, provided the `legacy` feature is
+//! enabled.
+//!
+//! Afterwards, the [`PanicPolicy`] set via [`set_policy`] decides how the partition continues. With
+//! the `std` feature enabled, the panic handler of `std` is used and the policy has no effect.
+//!
+//! # Examples
+//!
+#![cfg_attr(feature = "sim", doc = "```")]
+#![cfg_attr(not(feature = "sim"), doc = "```no_run")]
+//! use xng_rs::panic::{self, PanicPolicy};
+//!
+//! // a panic is fatal for this partition
+//! panic::set_policy(PanicPolicy::Halt);
+//! ```
 
-fn panic(info: &PanicInfo) -> ! {
-    let mut host_stderr = HStderr::new();
+use core::sync::atomic::{AtomicU8, Ordering};
 
-    // logs "panicked at '$reason', src/main.rs:27:4" to the host stderr
-    writeln!(host_stderr, "{}", info).ok();
+/// What happens after a panic was reported
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PanicPolicy {
+    /// Halt the partition
+    Halt,
 
-    loop {}
+    /// Restart the partition, keeping its memory contents
+    WarmRestart,
+
+    /// Restart the partition, reinitializing its memory contents
+    ColdRestart,
+
+    /// Yield every slot to the hypervisor, without ever continuing. This is the default.
+    ///
+    /// Without the `schedule` feature, the partition spins instead.
+    Idle,
+}
+
+/// The current panic policy
+static POLICY: AtomicU8 = AtomicU8::new(PanicPolicy::Idle as u8);
+
+/// Set what happens after a panic was reported
+pub fn set_policy(policy: PanicPolicy) {
+    POLICY.store(policy as u8, Ordering::Relaxed);
 }
 
+/// Get what happens after a panic was reported
+pub fn policy() -> PanicPolicy {
+    match POLICY.load(Ordering::Relaxed) {
+        p if p == PanicPolicy::Halt as u8 => PanicPolicy::Halt,
+        p if p == PanicPolicy::WarmRestart as u8 => PanicPolicy::WarmRestart,
+        p if p == PanicPolicy::ColdRestart as u8 => PanicPolicy::ColdRestart,
+        _ => PanicPolicy::Idle,
+    }
+}
+
+#[cfg(not(feature = "std"))]
+mod handler {
+    #[cfg(any(feature = "hm", feature = "legacy"))]
+    use core::fmt::{self, Write};
+    use core::panic::PanicInfo;
+
+    use super::{policy, PanicPolicy};
+    use crate::partition::{self, ResetMode};
+
+    #[panic_handler]
+    fn panic(info: &PanicInfo) -> ! {
+        report(info);
+
+        // errors can not be handled anymore, the partition idles if a policy fails
+        if let Ok(me) = partition::my_id() {
+            let _ = match policy() {
+                PanicPolicy::Halt => partition::halt(me),
+                PanicPolicy::WarmRestart => partition::reset(me, ResetMode::Warm),
+                PanicPolicy::ColdRestart => partition::reset(me, ResetMode::Cold),
+                PanicPolicy::Idle => Ok(()),
+            };
+        }
+
+        loop {
+            #[cfg(feature = "schedule")]
+            crate::vcpu::wait_until_next_schedule_slot();
+            #[cfg(not(feature = "schedule"))]
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Write the source location and the message of the panic
+    #[cfg(any(feature = "hm", feature = "legacy"))]
+    fn write_message(w: &mut impl Write, info: &PanicInfo) -> fmt::Result {
+        if let Some(location) = info.location() {
+            write!(
+                w,
+                "panicked at {}:{}:{}: ",
+                location.file(),
+                location.line(),
+                location.column()
+            )?;
+        }
+        write!(w, "{}", info.message())
+    }
+
+    /// Report the panic to the health monitor
+    #[cfg(feature = "hm")]
+    fn report(info: &PanicInfo) {
+        use crate::{
+            buf::FmtBuffer,
+            hm::{self, HmEvent},
+        };
+
+        let mut message = FmtBuffer::<{ hm::MAX_MESSAGE_LENGTH }>::new();
+        // the buffer truncates instead of failing
+        let _ = write_message(&mut message, info);
+        message.mark_truncation();
+
+        // there is nobody left to handle a failed report
+        let _ = hm::report(HmEvent::ApplicationError, message.as_bytes());
+    }
+
+    /// Write the panic to the console, as there is no health monitor
+    #[cfg(all(not(feature = "hm"), feature = "legacy"))]
+    fn report(info: &PanicInfo) {
+        let mut console = crate::console::Console::new();
+        // the console is flushed when dropped
+        let _ = write_message(&mut console, info);
+        let _ = writeln!(console);
+    }
+
+    /// Nothing to report the panic to
+    #[cfg(not(any(feature = "hm", feature = "legacy")))]
+    fn report(_info: &PanicInfo) {}
+}
+
+#[cfg(all(test, feature = "sim"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn policy_is_kept() {
+        // the only test touching the global policy, as tests run in parallel
+        assert_eq!(policy(), PanicPolicy::Idle);
+        for p in [
+            PanicPolicy::Halt,
+            PanicPolicy::WarmRestart,
+            PanicPolicy::ColdRestart,
+            PanicPolicy::Idle,
+        ] {
+            set_policy(p);
+            assert_eq!(policy(), p);
+        }
+    }
+}
@@ -14,7 +14,8 @@ pub type PartitionId = bindings::xPartitionId_t;
 ///
 /// # Examples
 ///
-/// ```no_run
+#[cfg_attr(feature = "sim", doc = "```")]
+#[cfg_attr(not(feature = "sim"), doc = "```no_run")]
 /// # fn main() -> Result<(), xng_rs::XngError> {
 /// use xng_rs::partition;
 ///
@@ -35,7 +36,8 @@ pub fn my_id() -> Result<PartitionId, XngError> {
 ///
 /// # Examples
 ///
-/// ```no_run
+#[cfg_attr(feature = "sim", doc = "```")]
+#[cfg_attr(not(feature = "sim"), doc = "```no_run")]
 /// # fn main() -> Result<(), xng_rs::XngError> {
 /// use xng_rs::partition;
 ///
@@ -75,6 +77,37 @@ pub fn halt(partition: PartitionId) -> Result<(), XngError> {
     XngError::from(return_code)
 }
 
+/// The way a partition is restarted
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum ResetMode {
+    /// Restart the partition, keeping its memory contents
+    Warm = bindings::xWarmReset,
+
+    /// Restart the partition, reinitializing its memory contents
+    Cold = bindings::xColdReset,
+}
+
+/// Restart a partition
+///
+/// # Examples
+///
+/// ```no_run
+/// # fn main() -> Result<(), xng_rs::XngError> {
+/// use xng_rs::partition::{self, ResetMode};
+///
+/// // Start over again
+/// let my_id = partition::my_id()?;
+/// partition::reset(my_id, ResetMode::Warm)?;
+/// # Ok(())}
+/// ```
+pub fn reset(partition: PartitionId, mode: ResetMode) -> Result<(), XngError> {
+    let return_code =
+        unsafe { bindings::XResetPartition(partition, mode as bindings::xResetMode_t, 0) };
+    XngError::from(return_code)
+}
+
+#[allow(dead_code)]
 struct PartitionStatus {
     /// The start condition field indicates the way the partition was started
     start_condition: u8,
@@ -86,6 +119,45 @@ struct PartitionStatus {
     /// When vCpuState is xVCpuRunning, vCpuSchedStatus contains the status of the current slot
     vcpu_state: crate::vcpu::VCpuState,
 
-    ///
+    /// The status of the current slot
     vcpu_sched_state: bindings::xVCpuSchedStatus_t,
 }
+
+#[cfg(all(test, feature = "sim"))]
+mod tests {
+    use super::*;
+    use crate::{cstr, sim};
+
+    #[test]
+    fn ids_of_the_simulated_partitions() {
+        sim::set_partition("Sensor", 3);
+        sim::add_partition("Actuator", 4);
+
+        assert_eq!(my_id().unwrap(), 3);
+        assert_eq!(id(cstr!("Actuator")).unwrap(), 4);
+        assert!(matches!(id(cstr!("Unknown")), Err(XngError::InvalidParam)));
+    }
+
+    #[test]
+    fn halt_another_partition() {
+        sim::add_partition("Actuator", 4);
+
+        halt(4).unwrap();
+        assert!(sim::is_halted(4));
+        assert!(matches!(halt(4), Err(XngError::NoAction)));
+        assert!(matches!(halt(5), Err(XngError::InvalidParam)));
+    }
+
+    #[test]
+    fn reset_another_partition() {
+        sim::add_partition("Actuator", 4);
+
+        reset(4, ResetMode::Warm).unwrap();
+        reset(4, ResetMode::Cold).unwrap();
+        assert_eq!(sim::restarts(4), 2);
+        assert!(matches!(
+            reset(5, ResetMode::Warm),
+            Err(XngError::InvalidParam)
+        ));
+    }
+}
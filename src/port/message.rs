@@ -0,0 +1,99 @@
+/// A message type which can be sent through a port
+///
+/// The size of a message is fixed by its byte representation `Bytes`, which usually is
+/// `[u8; SIZE]`. This allows the typed ports to derive their maximum message size `N` from the
+/// message type alone.
+///
+/// # Examples
+///
+/// ```
+/// use xng_rs::port::Message;
+///
+/// struct Altitude {
+///     meters: u32,
+///     valid: bool,
+/// }
+///
+/// impl Message for Altitude {
+///     type Bytes = [u8; 5];
+///
+///     fn zeroed() -> Self::Bytes {
+///         [0; 5]
+///     }
+///
+///     fn encode(&self) -> Self::Bytes {
+///         let mut bytes = [0; 5];
+///         bytes[..4].copy_from_slice(&self.meters.to_le_bytes());
+///         bytes[4] = self.valid as u8;
+///         bytes
+///     }
+///
+///     fn decode(bytes: &Self::Bytes) -> Option<Self> {
+///         let valid = match bytes[4] {
+///             0 => false,
+///             1 => true,
+///             _ => return None,
+///         };
+///         let meters = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+///         Some(Self { meters, valid })
+///     }
+/// }
+/// ```
+pub trait Message: Sized {
+    /// The raw representation of the message on the port
+    type Bytes: AsRef<[u8]> + AsMut<[u8]>;
+
+    /// Get an all zero byte representation, which serves as receive buffer
+    fn zeroed() -> Self::Bytes;
+
+    /// Encode the message into its byte representation
+    fn encode(&self) -> Self::Bytes;
+
+    /// Decode a message from its byte representation
+    ///
+    /// Returns `None` if `bytes` do not represent a valid message.
+    fn decode(bytes: &Self::Bytes) -> Option<Self>;
+
+    /// The size of one message in bytes
+    fn size() -> usize {
+        Self::zeroed().as_ref().len()
+    }
+}
+
+impl<const N: usize> Message for [u8; N] {
+    type Bytes = [u8; N];
+
+    fn zeroed() -> Self::Bytes {
+        [0; N]
+    }
+
+    fn encode(&self) -> Self::Bytes {
+        *self
+    }
+
+    fn decode(bytes: &Self::Bytes) -> Option<Self> {
+        Some(*bytes)
+    }
+}
+
+macro_rules! impl_message_for_int {
+    ($($t:ty),*) => {$(
+        impl Message for $t {
+            type Bytes = [u8; core::mem::size_of::<$t>()];
+
+            fn zeroed() -> Self::Bytes {
+                [0; core::mem::size_of::<$t>()]
+            }
+
+            fn encode(&self) -> Self::Bytes {
+                self.to_le_bytes()
+            }
+
+            fn decode(bytes: &Self::Bytes) -> Option<Self> {
+                Some(<$t>::from_le_bytes(*bytes))
+            }
+        }
+    )*};
+}
+
+impl_message_for_int!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
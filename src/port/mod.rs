@@ -11,16 +11,30 @@
 //! In contrast, a __Queuing Port__ retains the last `M` messages, of which each might be up to `N`
 //! bytes big. The messages are guaranteed to to be served in FIFO order. This type of port is
 //! single producer single consumer (SPSC), so only two partitions can use one Queueing Port.
+//!
+//! # Typed Ports
+//!
+//! Besides the raw byte oriented ports, there are typed variants (e.g. `TypedSamplingSender<T>`)
+//! which carry values of any type implementing [`Message`]. Their maximum message size is derived
+//! from the message type.
 
 use crate::bindings;
 
+mod message;
+#[cfg(feature = "queuing")]
+mod queuing;
+#[cfg(feature = "sampling")]
 mod sampling;
 
+pub use message::*;
+#[cfg(feature = "queuing")]
+pub use queuing::*;
+#[cfg(feature = "sampling")]
 pub use sampling::*;
 
 /// The direction of a port
-#[derive(PartialEq, Eq)]
-enum PortDirection {
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PortDirection {
     /// This port is a source
     Source = bindings::xSourcePort as isize,
     /// This port is a destination
@@ -30,6 +44,7 @@ enum PortDirection {
 /// Check if a message is valid
 ///
 /// Returns true if the message was valid
+#[cfg(feature = "sampling")]
 fn validity_to_bool(validity: bindings::xValidity_t) -> bool {
     match validity {
             bindings::xInvalidMessage => false,
@@ -40,3 +55,18 @@ fn validity_to_bool(validity: bindings::xValidity_t) -> bool {
               validity),
         }
 }
+
+/// Convert a raw `xPortDirection_t` into a `PortDirection`
+#[cfg(feature = "queuing")]
+fn direction_from_raw(direction: bindings::xPortDirection_t) -> PortDirection {
+    match direction {
+        bindings::xSourcePort => PortDirection::Source,
+        bindings::xDestinationPort => PortDirection::Destination,
+        _ => panic!(
+            "XNG broke it's contract, the value of `xPortDirection_t` is neither {} nor {}, but {}",
+            bindings::xSourcePort,
+            bindings::xDestinationPort,
+            direction
+        ),
+    }
+}
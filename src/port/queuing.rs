@@ -0,0 +1,332 @@
+use core::{ffi::c_void, future::poll_fn, mem::MaybeUninit, task::Poll};
+
+use cstr_core::CStr;
+
+use super::{direction_from_raw, PortDirection};
+use crate::{bindings, XngError};
+
+/// The type of a queuing ports id
+pub type QueuingPortId = bindings::xQueuingPortId_t;
+
+/// Receives up to `M` messages of up to `N` bytes each in FIFO order
+pub struct QueuingReceiver<const N: usize, const M: usize> {
+    port_id: QueuingPortId,
+}
+
+impl<const N: usize, const M: usize> QueuingReceiver<N, M> {
+    /// Creates a communication port operating in queuing mode
+    ///
+    /// # Arguments
+    ///
+    /// * `port_name` - The name of this port. Use the `cstr!("Hello world")` macro to create
+    ///   values from literals.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "sim", doc = "```")]
+    #[cfg_attr(not(feature = "sim"), doc = "```no_run")]
+    /// # fn main() -> Result<(), xng_rs::XngError> {
+    /// use xng_rs::prelude::*;
+    ///
+    /// let port = port::QueuingReceiver::<64, 8>::new(cstr!("telecommands"))?;
+    /// let mut buf = [0u8; 64];
+    /// if let Some(msg) = port.recv(&mut buf)? {
+    ///     // handle msg
+    /// }
+    /// # Ok(())}
+    /// ```
+    pub fn new(port_name: &CStr) -> Result<Self, XngError> {
+        let port_id = create_queuing_port::<N, M>(port_name, PortDirection::Destination)?;
+        Ok(Self { port_id })
+    }
+
+    /// Receives the oldest message from the queue
+    ///
+    /// Returns `Ok(Some(read_bytes))` if a message was available, `Ok(None)` if the queue is
+    /// empty and `Err(XngError)` if an error occured
+    pub fn recv<'a>(&self, buf: &'a mut [u8]) -> Result<Option<&'a mut [u8]>, XngError> {
+        // if buf is smaller than N bytes, we can not fit a full message in it; abort
+        if buf.len() < N {
+            return Err(XngError::BufTooSmall {
+                buf_size: buf.len(),
+                min_required: N,
+            });
+        }
+
+        match receive_queuing_message(self.port_id, buf)? {
+            Some(bytes_read) => Ok(Some(&mut buf[..bytes_read])),
+            None => Ok(None),
+        }
+    }
+
+    /// Receives the oldest message from the queue, waiting until one is available
+    ///
+    /// The queue is checked whenever the future is polled, see the [`executor`](crate::executor)
+    /// module.
+    pub async fn recv_async<'a>(&self, buf: &'a mut [u8]) -> Result<&'a mut [u8], XngError> {
+        if buf.len() < N {
+            return Err(XngError::BufTooSmall {
+                buf_size: buf.len(),
+                min_required: N,
+            });
+        }
+
+        let bytes_read = poll_fn(|_| match receive_queuing_message(self.port_id, buf) {
+            Ok(Some(bytes_read)) => Poll::Ready(Ok(bytes_read)),
+            Ok(None) => Poll::Pending,
+            Err(e) => Poll::Ready(Err(e)),
+        })
+        .await?;
+        Ok(&mut buf[..bytes_read])
+    }
+
+    /// Get the id of this queuing port
+    pub fn id(&self) -> QueuingPortId {
+        self.port_id
+    }
+
+    /// Get status of the port
+    pub fn status(&self) -> Result<QueuingPortStatus, XngError> {
+        QueuingPortStatus::new(self.port_id)
+    }
+}
+
+/// Appends messages of up to `N` bytes to a queue of depth `M`
+pub struct QueuingSender<const N: usize, const M: usize> {
+    port_id: QueuingPortId,
+}
+
+impl<const N: usize, const M: usize> QueuingSender<N, M> {
+    /// Creates a communication port operating in queuing mode
+    ///
+    /// # Arguments
+    ///
+    /// * `port_name` - The name of this port. Use the `cstr!("Hello world")` macro to create
+    ///   values from literals.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "sim", doc = "```")]
+    #[cfg_attr(not(feature = "sim"), doc = "```no_run")]
+    /// # fn main() -> Result<(), xng_rs::XngError> {
+    /// use xng_rs::prelude::*;
+    ///
+    /// let port = port::QueuingSender::<64, 8>::new(cstr!("telemetry"))?;
+    /// port.send(b"hello")?;
+    /// # Ok(())}
+    /// ```
+    pub fn new(port_name: &CStr) -> Result<Self, XngError> {
+        let port_id = create_queuing_port::<N, M>(port_name, PortDirection::Source)?;
+        Ok(Self { port_id })
+    }
+
+    /// Send a message
+    ///
+    /// Returns `Ok(())` on success. `buf` must be smaller or equal in size to `N`. If the queue
+    /// is full, `Err(XngError::NotAvailable)` is returned and the message is discarded.
+    pub fn send(&self, buf: &[u8]) -> Result<(), XngError> {
+        // if buf is bigger than N bytes, we can not fit the send the whole buffer; abort
+        if buf.len() > N {
+            return Err(XngError::BufTooBig {
+                buf_size: buf.len(),
+                max_allowed: N,
+            });
+        }
+
+        let return_code = unsafe {
+            bindings::XSendQueuingMessage(
+                self.port_id,
+                buf.as_ptr() as *mut c_void, // TODO fix to non mut pointer
+                buf.len() as u32,            // TODO fix to usize
+            )
+        };
+        XngError::from(return_code)
+    }
+
+    /// Get the id of this queuing port
+    pub fn id(&self) -> QueuingPortId {
+        self.port_id
+    }
+
+    /// Get status of the port
+    pub fn status(&self) -> Result<QueuingPortStatus, XngError> {
+        QueuingPortStatus::new(self.port_id)
+    }
+}
+
+/// The current status of a Queuing Port
+#[derive(Debug)]
+pub struct QueuingPortStatus {
+    /// Maximum size in bytes of one message as defined via XCF
+    pub max_message_size: usize,
+
+    /// Maximum number of messages the queue can hold as defined via XCF
+    pub max_messages: usize,
+
+    /// Number of messages currently in the queue
+    pub messages: usize,
+
+    /// Whether this end of the port is the source or the destination
+    pub direction: PortDirection,
+}
+
+impl QueuingPortStatus {
+    fn new(id: QueuingPortId) -> Result<QueuingPortStatus, XngError> {
+        let mut status_struct = MaybeUninit::uninit();
+
+        let status_struct = unsafe {
+            let return_code = bindings::XGetQueuingPortStatus(id, status_struct.as_mut_ptr());
+            XngError::from(return_code)?;
+            status_struct.assume_init()
+        };
+
+        Ok(Self {
+            max_message_size: status_struct.maxMessageSize as usize,
+            max_messages: status_struct.maxNoMessages as usize,
+            messages: status_struct.noMessages as usize,
+            direction: direction_from_raw(status_struct.portDirection),
+        })
+    }
+
+    /// Number of messages which can still be enqueued before the queue is full
+    pub fn free(&self) -> usize {
+        self.max_messages.saturating_sub(self.messages)
+    }
+
+    /// Whether the queue can not take any further message
+    pub fn is_full(&self) -> bool {
+        self.free() == 0
+    }
+
+    /// Whether there is no message in the queue
+    pub fn is_empty(&self) -> bool {
+        self.messages == 0
+    }
+}
+
+/// Create a queuing port for messages of up to `N` bytes and a queue depth of `M`
+fn create_queuing_port<const N: usize, const M: usize>(
+    port_name: &CStr,
+    direction: PortDirection,
+) -> Result<QueuingPortId, XngError> {
+    let mut port_id = MaybeUninit::uninit();
+
+    let return_code = unsafe {
+        bindings::XCreateQueuingPort(
+            port_name.as_ptr() as *mut cty::c_char, // TODO fix to non mut pointer
+            N as u32,                               // TODO fix to usize
+            M as u32,                               // TODO fix to usize
+            direction as u32,
+            port_id.as_mut_ptr(),
+        )
+    };
+
+    XngError::from(return_code)?;
+    Ok(unsafe { port_id.assume_init() })
+}
+
+/// Receive a message into `buf`, returning its size - None if the queue is empty
+fn receive_queuing_message(
+    port_id: QueuingPortId,
+    buf: &mut [u8],
+) -> Result<Option<usize>, XngError> {
+    let mut bytes_read = MaybeUninit::uninit();
+
+    let return_code = unsafe {
+        bindings::XReceiveQueuingMessage(
+            port_id,
+            buf.as_mut_ptr() as *mut c_void,
+            bytes_read.as_mut_ptr(), // TODO make this usize
+        )
+    };
+
+    // retrieve possible error
+    let error = XngError::from(return_code);
+    // handle NotAvailable special, as export the semantics of it via Option
+    if let Err(XngError::NotAvailable) = error {
+        return Ok(None);
+    }
+    // yield any other error
+    error?;
+
+    Ok(Some(unsafe { bytes_read.assume_init() as usize }))
+}
+
+#[cfg(all(test, feature = "sim"))]
+mod tests {
+    use super::*;
+    use crate::{cstr, sim};
+
+    #[test]
+    fn messages_are_received_in_order() {
+        sim::connect("telemetry_out", "telemetry_in");
+        let sender = QueuingSender::<4, 8>::new(cstr!("telemetry_out")).unwrap();
+        let receiver = QueuingReceiver::<4, 8>::new(cstr!("telemetry_in")).unwrap();
+        let mut buf = [0; 4];
+
+        assert_eq!(receiver.recv(&mut buf).unwrap(), None);
+
+        sender.send(&[1]).unwrap();
+        sender.send(&[2, 2]).unwrap();
+        assert_eq!(receiver.status().unwrap().messages, 2);
+        assert_eq!(receiver.recv(&mut buf).unwrap(), Some(&mut [1][..]));
+        assert_eq!(receiver.recv(&mut buf).unwrap(), Some(&mut [2, 2][..]));
+        assert_eq!(receiver.recv(&mut buf).unwrap(), None);
+    }
+
+    #[test]
+    fn full_queue_discards_messages() {
+        sim::connect("telemetry_out", "telemetry_in");
+        let sender = QueuingSender::<4, 2>::new(cstr!("telemetry_out")).unwrap();
+        let receiver = QueuingReceiver::<4, 2>::new(cstr!("telemetry_in")).unwrap();
+        let mut buf = [0; 4];
+
+        let status = sender.status().unwrap();
+        assert!(status.is_empty());
+        assert_eq!(status.free(), 2);
+        assert_eq!(status.max_message_size, 4);
+        assert_eq!(status.direction, PortDirection::Source);
+
+        sender.send(&[1]).unwrap();
+        sender.send(&[2]).unwrap();
+        assert!(sender.status().unwrap().is_full());
+        assert!(matches!(sender.send(&[3]), Err(XngError::NotAvailable)));
+
+        assert_eq!(receiver.recv(&mut buf).unwrap(), Some(&mut [1][..]));
+        assert_eq!(receiver.recv(&mut buf).unwrap(), Some(&mut [2][..]));
+        assert_eq!(receiver.recv(&mut buf).unwrap(), None);
+    }
+
+    #[test]
+    fn duplicate_port_creation() {
+        let _sender = QueuingSender::<4, 8>::new(cstr!("telemetry")).unwrap();
+
+        assert!(matches!(
+            QueuingSender::<4, 8>::new(cstr!("telemetry")),
+            Err(XngError::NoAction)
+        ));
+        assert!(matches!(
+            QueuingSender::<4, 16>::new(cstr!("telemetry")),
+            Err(XngError::InvalidConfig)
+        ));
+        assert!(matches!(
+            QueuingReceiver::<4, 8>::new(cstr!("telemetry")),
+            Err(XngError::InvalidConfig)
+        ));
+    }
+
+    #[test]
+    fn buffers_are_checked() {
+        let sender = QueuingSender::<4, 8>::new(cstr!("telemetry_out")).unwrap();
+        let receiver = QueuingReceiver::<4, 8>::new(cstr!("telemetry_in")).unwrap();
+
+        assert!(matches!(
+            sender.send(&[0; 5]),
+            Err(XngError::BufTooBig { .. })
+        ));
+        assert!(matches!(
+            receiver.recv(&mut [0; 3]),
+            Err(XngError::BufTooSmall { .. })
+        ));
+    }
+}
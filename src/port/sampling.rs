@@ -1,11 +1,18 @@
-use core::{ffi::c_void, mem::MaybeUninit};
+use core::{
+    ffi::c_void,
+    future::poll_fn,
+    marker::PhantomData,
+    mem::MaybeUninit,
+    sync::atomic::{AtomicI64, Ordering},
+    task::Poll,
+};
 
 use cstr_core::CStr;
 
-use super::{validity_to_bool, PortDirection};
+use super::{validity_to_bool, Message, PortDirection};
 use crate::{
     bindings,
-    time::{duration_from_xtime_t, Duration},
+    time::{duration_from_xtime_t, Duration, Instant},
     XngError,
 };
 
@@ -15,6 +22,8 @@ pub type SamplingPortId = bindings::xSamplingPortId_t;
 /// Keeps the last (if any) sent value
 pub struct SamplingReceiver<const N: usize> {
     port_id: SamplingPortId,
+    /// Raw timestamp of the message last received via `recv_async`, or [`NEVER_RECEIVED`]
+    last_received: AtomicI64,
 }
 
 impl<const N: usize> SamplingReceiver<N> {
@@ -23,26 +32,15 @@ impl<const N: usize> SamplingReceiver<N> {
     /// # Arguments
     ///
     /// * `port_name` - The name of this port. Use the `cstr!("Hello world")` macro to create
-    /// values from literals.
+    ///   values from literals.
     /// * `ttl` - Time to live of the message. The message will be valid for `ttl` microseconds
-    /// after it was written. Naturally, a duration below one microsecond is not supported.
+    ///   after it was written. Naturally, a duration below one microsecond is not supported.
     pub fn new<T: Into<Duration>>(port_name: &CStr, ttl: T) -> Result<Self, XngError> {
-        let mut port_id = MaybeUninit::uninit();
-
-        let return_code = unsafe {
-            bindings::XCreateSamplingPort(
-                port_name.as_ptr() as *mut cty::c_char, // TODO fix to non mut pointer
-                N as u32,
-                PortDirection::Destination as u32,
-                ttl.into().as_micros() as bindings::xTime_t,
-                port_id.as_mut_ptr(),
-            )
-        };
-
-        XngError::from(return_code)?;
-        let port_id = unsafe { port_id.assume_init() };
-
-        Ok(Self { port_id })
+        let port_id = create_sampling_port(port_name, N, PortDirection::Destination, ttl.into())?;
+        Ok(Self {
+            port_id,
+            last_received: AtomicI64::new(NEVER_RECEIVED),
+        })
     }
 
     /// Receives a message
@@ -58,34 +56,36 @@ impl<const N: usize> SamplingReceiver<N> {
             });
         }
 
-        let mut bytes_read = MaybeUninit::uninit();
-        let mut validity = MaybeUninit::uninit();
-
-        let return_code = unsafe {
-            bindings::XReadSamplingMessage(
-                self.port_id,
-                buf.as_mut_ptr() as *mut c_void,
-                bytes_read.as_mut_ptr(), // TODO make this usize
-                validity.as_mut_ptr(),
-            )
-        };
+        // No error, give back the result together with the validity
+        match read_sampling_message(self.port_id, buf)? {
+            Some((bytes_read, valid)) => Ok(Some((&mut buf[..bytes_read], valid))),
+            None => Ok(None),
+        }
+    }
 
-        // retrieve possible error
-        let error = XngError::from(return_code);
-        // handle NotAvailable special, as export the semantics of it via Option
-        if let Err(XngError::NotAvailable) = error {
-            return Ok(None);
+    /// Receives a message, waiting until a valid one is available which was written after the one
+    /// previously received via `recv_async`
+    ///
+    /// The port is checked whenever the future is polled, see the [`executor`](crate::executor)
+    /// module.
+    pub async fn recv_async<'a>(&self, buf: &'a mut [u8]) -> Result<&'a mut [u8], XngError> {
+        if buf.len() < N {
+            return Err(XngError::BufTooSmall {
+                buf_size: buf.len(),
+                min_required: N,
+            });
         }
-        // yield any other error
-        error?;
 
-        // No error, give back the result together with the validity
-        Ok(Some(unsafe {
-            (
-                &mut buf[..bytes_read.assume_init() as usize],
-                validity_to_bool(validity.assume_init()),
+        let bytes_read =
+            poll_fn(
+                |_| match read_new_sampling_message(self.port_id, buf, &self.last_received) {
+                    Ok(Some(bytes_read)) => Poll::Ready(Ok(bytes_read)),
+                    Ok(None) => Poll::Pending,
+                    Err(e) => Poll::Ready(Err(e)),
+                },
             )
-        }))
+            .await?;
+        Ok(&mut buf[..bytes_read])
     }
 
     /// Get the id of this sampling port
@@ -98,6 +98,60 @@ impl<const N: usize> SamplingReceiver<N> {
     pub fn status(&self) -> Result<SamplingPortStatus, XngError> {
         SamplingPortStatus::new(self.port_id)
     }
+
+    /// Receives a message together with its age, classified against the refresh period
+    ///
+    /// The message is considered fresh if it is not older than the refresh period configured via
+    /// XCF. See [`SamplingReceiver::recv_sample_within`] to supply a custom maximum age.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "sim", doc = "```")]
+    #[cfg_attr(not(feature = "sim"), doc = "```no_run")]
+    /// # fn main() -> Result<(), xng_rs::XngError> {
+    /// use xng_rs::prelude::*;
+    /// use xng_rs::port::Sample;
+    /// use xng_rs::time::DurationFromInt;
+    ///
+    /// let port = port::SamplingReceiver::<64>::new(cstr!("sensor_data"), 10u32.ms())?;
+    /// let mut buf = [0u8; 64];
+    /// match port.recv_sample(&mut buf)? {
+    ///     Sample::Fresh { message, age } => { /* use message */ }
+    ///     Sample::Stale { message, age } => { /* degrade */ }
+    ///     Sample::NeverWritten => { /* wait for producer */ }
+    /// }
+    /// # Ok(())}
+    /// ```
+    pub fn recv_sample<'a>(&self, buf: &'a mut [u8]) -> Result<Sample<&'a mut [u8]>, XngError> {
+        let status = self.status()?;
+        let max_age = status.refresh_period;
+        self.sample(buf, status, max_age)
+    }
+
+    /// Receives a message together with its age, classified against `max_age`
+    pub fn recv_sample_within<'a, D: Into<Duration>>(
+        &self,
+        buf: &'a mut [u8],
+        max_age: D,
+    ) -> Result<Sample<&'a mut [u8]>, XngError> {
+        let status = self.status()?;
+        self.sample(buf, status, max_age.into())
+    }
+
+    fn sample<'a>(
+        &self,
+        buf: &'a mut [u8],
+        status: SamplingPortStatus,
+        max_age: Duration,
+    ) -> Result<Sample<&'a mut [u8]>, XngError> {
+        // the status is retrieved before the message, so that a message written in between is
+        // considered older than it is - never younger
+        let now = Instant::now()?;
+        match self.recv(buf)? {
+            Some((message, _)) => Ok(Sample::classify(message, &status, now, max_age)),
+            None => Ok(Sample::NeverWritten),
+        }
+    }
 }
 
 /// Allows to store one message in the port
@@ -111,23 +165,14 @@ impl<const N: usize> SamplingSender<N> {
     /// # Arguments
     ///
     /// * `port_name` - The name of this port. Use the `csrt!("Hello world")` macro to create
-    /// values from literals.
+    ///   values from literals.
     pub fn new(port_name: &CStr) -> Result<Self, XngError> {
-        let mut port_id = MaybeUninit::uninit();
-
-        let return_code = unsafe {
-            bindings::XCreateSamplingPort(
-                port_name.as_ptr() as *mut cty::c_char, // TODO fix to non mut pointer
-                N as u32,                               // fix to usize
-                PortDirection::Source as u32,           // TODO fix to usize
-                1 as bindings::xTime_t,
-                port_id.as_mut_ptr(),
-            )
-        };
-
-        XngError::from(return_code)?;
-        let port_id = unsafe { port_id.assume_init() };
-
+        let port_id = create_sampling_port(
+            port_name,
+            N,
+            PortDirection::Source,
+            Duration::from_micros(1),
+        )?;
         Ok(Self { port_id })
     }
 
@@ -143,14 +188,109 @@ impl<const N: usize> SamplingSender<N> {
             });
         }
 
-        let return_code = unsafe {
-            bindings::XWriteSamplingMessage(
-                self.port_id,
-                buf.as_ptr() as *mut c_void, // TODO fix to non mut pointer
-                buf.len() as u32,            // TODO fix to usize
-            )
+        write_sampling_message(self.port_id, buf)
+    }
+
+    /// Get the id of this sampling port
+    pub fn id(&self) -> SamplingPortId {
+        self.port_id
+    }
+
+    /// Get status of the port
+    pub fn status(&self) -> Result<SamplingPortStatus, XngError> {
+        SamplingPortStatus::new(self.port_id)
+    }
+}
+
+/// Keeps the last (if any) sent message of type `T`
+///
+/// In contrast to [`SamplingReceiver`] the maximum message size is derived from `T`, and every
+/// received message is decoded into a `T`.
+pub struct TypedSamplingReceiver<T: Message> {
+    port_id: SamplingPortId,
+    /// Raw timestamp of the message last received via `recv_async`, or [`NEVER_RECEIVED`]
+    last_received: AtomicI64,
+    _message: PhantomData<T>,
+}
+
+impl<T: Message> TypedSamplingReceiver<T> {
+    /// Creates a communication port operating in sampling mode
+    ///
+    /// # Arguments
+    ///
+    /// * `port_name` - The name of this port. Use the `cstr!("Hello world")` macro to create
+    ///   values from literals.
+    /// * `ttl` - Time to live of the message. The message will be valid for `ttl` microseconds
+    ///   after it was written. Naturally, a duration below one microsecond is not supported.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "sim", doc = "```")]
+    #[cfg_attr(not(feature = "sim"), doc = "```no_run")]
+    /// # fn main() -> Result<(), xng_rs::XngError> {
+    /// use xng_rs::prelude::*;
+    /// use xng_rs::time::DurationFromInt;
+    ///
+    /// let port = port::TypedSamplingReceiver::<u32>::new(cstr!("altitude"), 100u32.ms())?;
+    /// if let Some((altitude, valid)) = port.recv()? {
+    ///     // handle altitude
+    /// }
+    /// # Ok(())}
+    /// ```
+    pub fn new<D: Into<Duration>>(port_name: &CStr, ttl: D) -> Result<Self, XngError> {
+        let port_id =
+            create_sampling_port(port_name, T::size(), PortDirection::Destination, ttl.into())?;
+        Ok(Self {
+            port_id,
+            last_received: AtomicI64::new(NEVER_RECEIVED),
+            _message: PhantomData,
+        })
+    }
+
+    /// Receives a message
+    ///
+    /// Returns `Ok(Some((message, validity)))` if a message was available, `Ok(None)` if no
+    /// message was available and `Err(XngError)` if an error occured. A message which is not
+    /// exactly of the size of `T` yields `Err(XngError::MessageLength { .. })`.
+    pub fn recv(&self) -> Result<Option<(T, bool)>, XngError> {
+        let mut bytes = T::zeroed();
+
+        let (bytes_read, valid) = match read_sampling_message(self.port_id, bytes.as_mut())? {
+            Some(result) => result,
+            None => return Ok(None),
         };
-        XngError::from(return_code)
+
+        Ok(Some((Self::decode(&bytes, bytes_read)?, valid)))
+    }
+
+    /// Receives a message, waiting until a valid one is available which was written after the one
+    /// previously received via `recv_async`
+    ///
+    /// The port is checked whenever the future is polled, see the [`executor`](crate::executor)
+    /// module.
+    pub async fn recv_async(&self) -> Result<T, XngError> {
+        let mut bytes = T::zeroed();
+
+        let bytes_read = poll_fn(|_| {
+            match read_new_sampling_message(self.port_id, bytes.as_mut(), &self.last_received) {
+                Ok(Some(bytes_read)) => Poll::Ready(Ok(bytes_read)),
+                Ok(None) => Poll::Pending,
+                Err(e) => Poll::Ready(Err(e)),
+            }
+        })
+        .await?;
+        Self::decode(&bytes, bytes_read)
+    }
+
+    fn decode(bytes: &T::Bytes, bytes_read: usize) -> Result<T, XngError> {
+        if bytes_read != T::size() {
+            return Err(XngError::MessageLength {
+                expected: T::size(),
+                received: bytes_read,
+            });
+        }
+
+        T::decode(bytes).ok_or(XngError::MalformedMessage)
     }
 
     /// Get the id of this sampling port
@@ -162,6 +302,157 @@ impl<const N: usize> SamplingSender<N> {
     pub fn status(&self) -> Result<SamplingPortStatus, XngError> {
         SamplingPortStatus::new(self.port_id)
     }
+
+    /// Receives a message together with its age, classified against the refresh period
+    ///
+    /// The message is considered fresh if it is not older than the refresh period configured via
+    /// XCF. See [`TypedSamplingReceiver::recv_sample_within`] to supply a custom maximum age.
+    pub fn recv_sample(&self) -> Result<Sample<T>, XngError> {
+        let status = self.status()?;
+        let max_age = status.refresh_period;
+        self.sample(status, max_age)
+    }
+
+    /// Receives a message together with its age, classified against `max_age`
+    pub fn recv_sample_within<D: Into<Duration>>(&self, max_age: D) -> Result<Sample<T>, XngError> {
+        let status = self.status()?;
+        self.sample(status, max_age.into())
+    }
+
+    fn sample(&self, status: SamplingPortStatus, max_age: Duration) -> Result<Sample<T>, XngError> {
+        // the status is retrieved before the message, so that a message written in between is
+        // considered older than it is - never younger
+        let now = Instant::now()?;
+        match self.recv()? {
+            Some((message, _)) => Ok(Sample::classify(message, &status, now, max_age)),
+            None => Ok(Sample::NeverWritten),
+        }
+    }
+}
+
+/// Allows to store one message of type `T` in the port
+///
+/// In contrast to [`SamplingSender`] the maximum message size is derived from `T`, and every
+/// message is encoded from a `T`.
+pub struct TypedSamplingSender<T: Message> {
+    port_id: SamplingPortId,
+    _message: PhantomData<T>,
+}
+
+impl<T: Message> TypedSamplingSender<T> {
+    /// Creates a communication port operating in sampling mode
+    ///
+    /// # Arguments
+    ///
+    /// * `port_name` - The name of this port. Use the `cstr!("Hello world")` macro to create
+    ///   values from literals.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "sim", doc = "```")]
+    #[cfg_attr(not(feature = "sim"), doc = "```no_run")]
+    /// # fn main() -> Result<(), xng_rs::XngError> {
+    /// use xng_rs::prelude::*;
+    ///
+    /// let port = port::TypedSamplingSender::<u32>::new(cstr!("altitude"))?;
+    /// port.send(&1337)?;
+    /// # Ok(())}
+    /// ```
+    pub fn new(port_name: &CStr) -> Result<Self, XngError> {
+        let port_id = create_sampling_port(
+            port_name,
+            T::size(),
+            PortDirection::Source,
+            Duration::from_micros(1),
+        )?;
+        Ok(Self {
+            port_id,
+            _message: PhantomData,
+        })
+    }
+
+    /// Send a message
+    pub fn send(&self, message: &T) -> Result<(), XngError> {
+        write_sampling_message(self.port_id, message.encode().as_ref())
+    }
+
+    /// Get the id of this sampling port
+    pub fn id(&self) -> SamplingPortId {
+        self.port_id
+    }
+
+    /// Get status of the port
+    pub fn status(&self) -> Result<SamplingPortStatus, XngError> {
+        SamplingPortStatus::new(self.port_id)
+    }
+}
+
+/// A message read from a sampling port, classified by its age
+#[derive(Debug)]
+pub enum Sample<M> {
+    /// The message is not older than the maximum age
+    Fresh {
+        /// The message
+        message: M,
+        /// Time passed since the message was written
+        age: Duration,
+    },
+
+    /// The message is older than the maximum age
+    Stale {
+        /// The message
+        message: M,
+        /// Time passed since the message was written
+        age: Duration,
+    },
+
+    /// No message was ever written to the port
+    NeverWritten,
+}
+
+impl<M> Sample<M> {
+    fn classify(message: M, status: &SamplingPortStatus, now: Instant, max_age: Duration) -> Self {
+        // a message without timestamp was written after the status was retrieved
+        let age = status
+            .last_message_ts
+            .map(|ts| now.duration_since(ts))
+            .unwrap_or_default();
+
+        if age <= max_age {
+            Sample::Fresh { message, age }
+        } else {
+            Sample::Stale { message, age }
+        }
+    }
+
+    /// Whether this is a fresh message
+    pub fn is_fresh(&self) -> bool {
+        matches!(self, Sample::Fresh { .. })
+    }
+
+    /// Get the age of the message, if any
+    pub fn age(&self) -> Option<Duration> {
+        match self {
+            Sample::Fresh { age, .. } | Sample::Stale { age, .. } => Some(*age),
+            Sample::NeverWritten => None,
+        }
+    }
+
+    /// Get the message, regardless of its freshness
+    pub fn message(self) -> Option<M> {
+        match self {
+            Sample::Fresh { message, .. } | Sample::Stale { message, .. } => Some(message),
+            Sample::NeverWritten => None,
+        }
+    }
+
+    /// Get the message only if it is fresh
+    pub fn fresh(self) -> Option<M> {
+        match self {
+            Sample::Fresh { message, .. } => Some(message),
+            _ => None,
+        }
+    }
 }
 
 /// The current status of a Sampling Port
@@ -171,7 +462,7 @@ pub struct SamplingPortStatus {
     pub refresh_period: Duration,
 
     /// Timestamp of last message - None if no message ever was received priorly
-    pub last_message_ts: Option<Duration>,
+    pub last_message_ts: Option<Instant>,
 
     /// Size in bytes of the last message which was received
     pub last_message_size: usize,
@@ -192,9 +483,299 @@ impl SamplingPortStatus {
 
         Ok(Self {
             refresh_period: duration_from_xtime_t(status_struct.refreshPeriod)?,
-            last_message_ts: duration_from_xtime_t(status_struct.lastMessageTimestamp).ok(),
+            last_message_ts: Instant::from_xtime_t(status_struct.lastMessageTimestamp).ok(),
             last_message_size: status_struct.lastMessageSize as usize,
             last_message_valid: validity_to_bool(status_struct.lastMessageValidity),
         })
     }
 }
+
+/// Create a sampling port for messages of up to `size` bytes
+fn create_sampling_port(
+    port_name: &CStr,
+    size: usize,
+    direction: PortDirection,
+    ttl: Duration,
+) -> Result<SamplingPortId, XngError> {
+    let mut port_id = MaybeUninit::uninit();
+
+    let return_code = unsafe {
+        bindings::XCreateSamplingPort(
+            port_name.as_ptr() as *mut cty::c_char, // TODO fix to non mut pointer
+            size as u32,                            // TODO fix to usize
+            direction as u32,                       // TODO fix to usize
+            ttl.as_micros() as bindings::xTime_t,
+            port_id.as_mut_ptr(),
+        )
+    };
+
+    XngError::from(return_code)?;
+    Ok(unsafe { port_id.assume_init() })
+}
+
+/// Read the message of a sampling port into `buf`
+///
+/// Returns `Ok(Some((bytes_read, validity)))` if a message was available and `Ok(None)` if not.
+fn read_sampling_message(
+    port_id: SamplingPortId,
+    buf: &mut [u8],
+) -> Result<Option<(usize, bool)>, XngError> {
+    let mut bytes_read = MaybeUninit::uninit();
+    let mut validity = MaybeUninit::uninit();
+
+    let return_code = unsafe {
+        bindings::XReadSamplingMessage(
+            port_id,
+            buf.as_mut_ptr() as *mut c_void,
+            bytes_read.as_mut_ptr(), // TODO make this usize
+            validity.as_mut_ptr(),
+        )
+    };
+
+    // retrieve possible error
+    let error = XngError::from(return_code);
+    // handle NotAvailable special, as export the semantics of it via Option
+    if let Err(XngError::NotAvailable) = error {
+        return Ok(None);
+    }
+    // yield any other error
+    error?;
+
+    Ok(Some(unsafe {
+        (
+            bytes_read.assume_init() as usize,
+            validity_to_bool(validity.assume_init()),
+        )
+    }))
+}
+
+/// The raw timestamp of the message last received if there is none, as no message is written
+/// before boot
+const NEVER_RECEIVED: bindings::xTime_t = -1;
+
+/// Read the message of a sampling port into `buf`, if it is valid and was written after the one
+/// last received
+///
+/// Returns `Ok(Some(bytes_read))` if there is such a message and `Ok(None)` if not. On success,
+/// `last_received` is updated to the timestamp of the message.
+fn read_new_sampling_message(
+    port_id: SamplingPortId,
+    buf: &mut [u8],
+    last_received: &AtomicI64,
+) -> Result<Option<usize>, XngError> {
+    // the status is retrieved before the message, so that a message written in between is
+    // received once more - rather than missed
+    let timestamp = match SamplingPortStatus::new(port_id)?.last_message_ts {
+        Some(timestamp) => timestamp.as_xtime_t(),
+        None => return Ok(None),
+    };
+    if timestamp == last_received.load(Ordering::Relaxed) {
+        return Ok(None);
+    }
+
+    match read_sampling_message(port_id, buf)? {
+        Some((bytes_read, true)) => {
+            last_received.store(timestamp, Ordering::Relaxed);
+            Ok(Some(bytes_read))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Write `buf` as the new message of a sampling port
+fn write_sampling_message(port_id: SamplingPortId, buf: &[u8]) -> Result<(), XngError> {
+    let return_code = unsafe {
+        bindings::XWriteSamplingMessage(
+            port_id,
+            buf.as_ptr() as *mut c_void, // TODO fix to non mut pointer
+            buf.len() as u32,            // TODO fix to usize
+        )
+    };
+    XngError::from(return_code)
+}
+
+#[cfg(all(test, feature = "sim"))]
+mod tests {
+    use core::{
+        future::Future,
+        pin::{pin, Pin},
+        task::{Context, Poll, Waker},
+    };
+
+    use super::*;
+    use crate::{cstr, sim, time::DurationFromInt};
+
+    fn poll<F: Future>(future: Pin<&mut F>) -> Poll<F::Output> {
+        future.poll(&mut Context::from_waker(Waker::noop()))
+    }
+
+    #[test]
+    fn message_is_valid_within_ttl() {
+        sim::connect("altitude_out", "altitude_in");
+        let sender = SamplingSender::<4>::new(cstr!("altitude_out")).unwrap();
+        let receiver = SamplingReceiver::<4>::new(cstr!("altitude_in"), 10u32.ms()).unwrap();
+        let mut buf = [0; 4];
+
+        assert_eq!(receiver.recv(&mut buf).unwrap(), None);
+
+        sender.send(&[1, 2, 3]).unwrap();
+        sim::advance(5u32.ms());
+        assert_eq!(
+            receiver.recv(&mut buf).unwrap(),
+            Some((&mut [1, 2, 3][..], true))
+        );
+
+        sim::advance(10u32.ms());
+        assert_eq!(
+            receiver.recv(&mut buf).unwrap(),
+            Some((&mut [1, 2, 3][..], false))
+        );
+    }
+
+    #[test]
+    fn duplicate_port_creation() {
+        let _receiver = SamplingReceiver::<4>::new(cstr!("altitude"), 10u32.ms()).unwrap();
+
+        assert!(matches!(
+            SamplingReceiver::<4>::new(cstr!("altitude"), 10u32.ms()),
+            Err(XngError::NoAction)
+        ));
+        assert!(matches!(
+            SamplingReceiver::<8>::new(cstr!("altitude"), 10u32.ms()),
+            Err(XngError::InvalidConfig)
+        ));
+        assert!(matches!(
+            SamplingSender::<4>::new(cstr!("altitude")),
+            Err(XngError::InvalidConfig)
+        ));
+    }
+
+    #[test]
+    fn buffers_are_checked() {
+        let sender = SamplingSender::<4>::new(cstr!("altitude_out")).unwrap();
+        let receiver = SamplingReceiver::<4>::new(cstr!("altitude_in"), 10u32.ms()).unwrap();
+
+        assert!(matches!(
+            sender.send(&[0; 5]),
+            Err(XngError::BufTooBig { .. })
+        ));
+        assert!(matches!(
+            receiver.recv(&mut [0; 3]),
+            Err(XngError::BufTooSmall { .. })
+        ));
+    }
+
+    #[test]
+    fn sample_is_classified_by_age() {
+        sim::connect("altitude_out", "altitude_in");
+        let sender = TypedSamplingSender::<u32>::new(cstr!("altitude_out")).unwrap();
+        let receiver = TypedSamplingReceiver::<u32>::new(cstr!("altitude_in"), 10u32.ms()).unwrap();
+
+        assert!(matches!(
+            receiver.recv_sample().unwrap(),
+            Sample::NeverWritten
+        ));
+
+        sender.send(&7).unwrap();
+        sim::advance(4u32.ms());
+        let sample = receiver.recv_sample().unwrap();
+        assert!(sample.is_fresh());
+        assert_eq!(sample.age(), Some(4u32.ms()));
+        assert!(!receiver.recv_sample_within(3u32.ms()).unwrap().is_fresh());
+
+        sim::advance(10u32.ms());
+        assert_eq!(receiver.recv_sample().unwrap().fresh(), None);
+    }
+
+    #[test]
+    fn typed_messages_are_decoded() {
+        sim::connect("altitude_out", "altitude_in");
+        let sender = TypedSamplingSender::<u32>::new(cstr!("altitude_out")).unwrap();
+        let receiver = TypedSamplingReceiver::<u32>::new(cstr!("altitude_in"), 10u32.ms()).unwrap();
+
+        assert_eq!(receiver.recv().unwrap(), None);
+        sender.send(&1337).unwrap();
+        assert_eq!(receiver.recv().unwrap(), Some((1337, true)));
+    }
+
+    #[test]
+    fn typed_message_of_wrong_length_is_rejected() {
+        sim::connect("altitude_out", "altitude_in");
+        let sender = SamplingSender::<4>::new(cstr!("altitude_out")).unwrap();
+        let receiver = TypedSamplingReceiver::<u32>::new(cstr!("altitude_in"), 10u32.ms()).unwrap();
+
+        sender.send(&[1, 2, 3]).unwrap();
+        assert!(matches!(
+            receiver.recv(),
+            Err(XngError::MessageLength {
+                expected: 4,
+                received: 3
+            })
+        ));
+    }
+
+    #[test]
+    fn receivers_are_sync() {
+        fn assert_sync<T: Sync>() {}
+        assert_sync::<SamplingReceiver<4>>();
+        assert_sync::<TypedSamplingReceiver<u32>>();
+    }
+
+    #[test]
+    fn recv_async_waits_for_a_new_message() {
+        sim::connect("altitude_out", "altitude_in");
+        let sender = SamplingSender::<4>::new(cstr!("altitude_out")).unwrap();
+        let receiver = SamplingReceiver::<4>::new(cstr!("altitude_in"), 10u32.ms()).unwrap();
+        let mut buf = [0; 4];
+
+        sender.send(&[1; 4]).unwrap();
+        let first = poll(pin!(receiver.recv_async(&mut buf)));
+        assert!(matches!(first, Poll::Ready(Ok([1, 1, 1, 1]))));
+
+        // the message is still valid, but was already received
+        let mut second = pin!(receiver.recv_async(&mut buf));
+        assert!(poll(second.as_mut()).is_pending());
+        sim::advance(1u32.ms());
+        assert!(poll(second.as_mut()).is_pending());
+
+        sender.send(&[2; 4]).unwrap();
+        assert!(matches!(poll(second), Poll::Ready(Ok([2, 2, 2, 2]))));
+    }
+
+    #[test]
+    fn typed_recv_async_waits_for_a_new_message() {
+        sim::connect("altitude_out", "altitude_in");
+        let sender = TypedSamplingSender::<u32>::new(cstr!("altitude_out")).unwrap();
+        let receiver = TypedSamplingReceiver::<u32>::new(cstr!("altitude_in"), 10u32.ms()).unwrap();
+
+        sender.send(&1).unwrap();
+        assert!(matches!(
+            poll(pin!(receiver.recv_async())),
+            Poll::Ready(Ok(1))
+        ));
+
+        let mut second = pin!(receiver.recv_async());
+        assert!(poll(second.as_mut()).is_pending());
+        sim::advance(1u32.ms());
+        assert!(poll(second.as_mut()).is_pending());
+
+        sender.send(&2).unwrap();
+        assert!(matches!(poll(second), Poll::Ready(Ok(2))));
+    }
+
+    #[test]
+    fn recv_async_skips_invalid_messages() {
+        sim::connect("altitude_out", "altitude_in");
+        let sender = SamplingSender::<4>::new(cstr!("altitude_out")).unwrap();
+        let receiver = SamplingReceiver::<4>::new(cstr!("altitude_in"), 10u32.ms()).unwrap();
+        let mut buf = [0; 4];
+
+        sender.send(&[1; 4]).unwrap();
+        sim::advance(20u32.ms());
+        let mut future = pin!(receiver.recv_async(&mut buf));
+        assert!(poll(future.as_mut()).is_pending());
+
+        sender.send(&[2; 4]).unwrap();
+        assert!(matches!(poll(future), Poll::Ready(Ok([2, 2, 2, 2]))));
+    }
+}
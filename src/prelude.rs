@@ -5,8 +5,19 @@
 
 pub use cstr_core::{self, CStr};
 
+#[cfg(feature = "hm")]
+pub use crate::hm;
+#[cfg(feature = "schedule")]
+pub use crate::schedule;
+#[cfg(feature = "vtimer")]
+pub use crate::timer;
+#[cfg(feature = "trace")]
+pub use crate::trace;
+#[cfg(feature = "virq")]
+pub use crate::virq;
+
 pub use crate::{
     cstr, partition, port,
-    time::{self, Duration},
+    time::{self, Duration, Instant},
     XngError,
 };
@@ -0,0 +1,133 @@
+//! Functions related to the scheduling plans
+//!
+//! XNG runs the partitions according to a cyclic scheduling plan, as defined via XCF. A system may
+//! define several plans, e.g. one per flight phase, and switch between them. A requested switch
+//! takes effect at the end of the current major frame.
+//!
+//! # Examples
+//!
+#![cfg_attr(feature = "sim", doc = "```")]
+#![cfg_attr(not(feature = "sim"), doc = "```no_run")]
+//! # fn main() -> Result<(), xng_rs::XngError> {
+//! use xng_rs::schedule::{self, PlanId};
+//!
+//! const CRUISE: PlanId = PlanId(2);
+//!
+//! if schedule::current_plan()? != CRUISE {
+//!     schedule::switch_plan(CRUISE)?;
+//! }
+//! # Ok(())}
+//! ```
+
+use core::mem::MaybeUninit;
+
+pub use crate::vcpu::SlotId;
+use crate::{bindings, time::Instant, XngError};
+
+/// The id of a scheduling plan, as defined via XCF
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PlanId(pub bindings::xcfPlanId_t);
+
+/// The current status of the scheduling plans
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScheduleStatus {
+    /// The plan in effect
+    pub current_plan: PlanId,
+
+    /// The plan in effect after the current major frame - equal to `current_plan` unless a switch
+    /// is pending
+    pub next_plan: PlanId,
+
+    /// When the current plan came into effect - None if the plan never changed since boot
+    pub last_plan_switch: Option<Instant>,
+}
+
+impl ScheduleStatus {
+    /// Whether a plan switch is pending
+    pub fn is_switch_pending(&self) -> bool {
+        self.current_plan != self.next_plan
+    }
+}
+
+/// Get the current status of the scheduling plans
+pub fn status() -> Result<ScheduleStatus, XngError> {
+    let mut status = MaybeUninit::uninit();
+
+    let status = unsafe {
+        let return_code = bindings::XGetScheduleStatus(status.as_mut_ptr());
+        XngError::from(return_code)?;
+        status.assume_init()
+    };
+
+    Ok(ScheduleStatus {
+        current_plan: PlanId(status.currentPlanId),
+        next_plan: PlanId(status.nextPlanId),
+        last_plan_switch: Instant::from_xtime_t(status.lastPlanSwitchTime).ok(),
+    })
+}
+
+/// Get the plan in effect
+pub fn current_plan() -> Result<PlanId, XngError> {
+    Ok(status()?.current_plan)
+}
+
+/// Get the plan in effect after the current major frame
+pub fn next_plan() -> Result<PlanId, XngError> {
+    Ok(status()?.next_plan)
+}
+
+/// Get when the current plan came into effect - None if the plan never changed since boot
+pub fn last_plan_switch() -> Result<Option<Instant>, XngError> {
+    Ok(status()?.last_plan_switch)
+}
+
+/// Request to switch to the plan `plan` at the end of the current major frame
+///
+/// Only partitions permitted via XCF may switch plans. Returns `Err(XngError::NoAction)` if `plan`
+/// is already the next plan and `Err(XngError::InvalidParam)` if there is no such plan.
+pub fn switch_plan(plan: PlanId) -> Result<(), XngError> {
+    let return_code = unsafe { bindings::XSwitchSchedulingPlan(plan.0) };
+    XngError::from(return_code)
+}
+
+#[cfg(all(test, feature = "sim"))]
+mod tests {
+    use super::*;
+    use crate::{sim, time::DurationFromInt, vcpu};
+
+    #[test]
+    fn switch_takes_effect_with_the_next_major_frame() {
+        assert_eq!(status().unwrap().last_plan_switch, None);
+
+        switch_plan(PlanId(2)).unwrap();
+        let pending = status().unwrap();
+        assert_eq!(pending.current_plan, PlanId(0));
+        assert_eq!(pending.next_plan, PlanId(2));
+        assert!(pending.is_switch_pending());
+        assert!(matches!(switch_plan(PlanId(2)), Err(XngError::NoAction)));
+
+        sim::advance(1u32.ms());
+        vcpu::wait_until_next_schedule_slot();
+        let switched = status().unwrap();
+        assert_eq!(switched.current_plan, PlanId(2));
+        assert!(!switched.is_switch_pending());
+        assert_eq!(switched.last_plan_switch, Some(Instant::now().unwrap()));
+    }
+
+    #[test]
+    fn unknown_plans_are_rejected() {
+        sim::Simulator::new()
+            .partition("ModeManager", 1, || {
+                assert!(matches!(
+                    switch_plan(PlanId(3)),
+                    Err(XngError::InvalidParam)
+                ));
+                assert_eq!(next_plan().unwrap(), PlanId(1));
+            })
+            .plan(1)
+            .slot(1, 5u32.ms())
+            .plan(2)
+            .slot(1, 5u32.ms())
+            .run(1);
+    }
+}
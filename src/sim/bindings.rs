@@ -0,0 +1,662 @@
+//! Rust implementation of the subset of the XNG C ABI used by this crate
+//!
+//! The names and signatures mirror the ones generated by bindgen from the XNG headers, so that the
+//! rest of the crate does not need to know whether it runs on XNG or in the simulation.
+
+#![allow(clippy::missing_safety_doc)]
+#![allow(dead_code)]
+#![allow(missing_docs)]
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+use std::{collections::VecDeque, ffi::CStr, slice};
+
+use cty::{c_char, c_void};
+
+use super::{with_state, Port, QueuingChannel, SamplingChannel, State, Timer};
+
+pub type xReturnCode_t = u32;
+pub const xNoError: xReturnCode_t = 0;
+pub const xNoAction: xReturnCode_t = 1;
+pub const xNotAvailable: xReturnCode_t = 2;
+pub const xInvalidParam: xReturnCode_t = 3;
+pub const xInvalidConfig: xReturnCode_t = 4;
+pub const xInvalidMode: xReturnCode_t = 5;
+
+pub type xTime_t = i64;
+pub type xTimeSpan_t = i64;
+
+pub type xPartitionId_t = i32;
+pub type xPartitionRestartRange_t = u32;
+
+pub type xResetMode_t = u32;
+pub const xWarmReset: xResetMode_t = 0;
+pub const xColdReset: xResetMode_t = 1;
+
+pub type xVCpuId_t = u32;
+pub type xVCpuState_t = u32;
+pub const xVCpuReady: xVCpuState_t = 0;
+pub const xVCpuRunning: xVCpuState_t = 1;
+pub const xVCpuIdle: xVCpuState_t = 2;
+pub const xVCpuSuspended: xVCpuState_t = 3;
+pub const xVCpuWaiting: xVCpuState_t = 4;
+
+pub type xcfSlotId_t = u32;
+pub type xcfPlanId_t = u32;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct xScheduleStatus_t {
+    pub currentPlanId: xcfPlanId_t,
+    pub nextPlanId: xcfPlanId_t,
+    pub lastPlanSwitchTime: xTime_t,
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct xVCpuSchedStatus_t {
+    pub slotId: xcfSlotId_t,
+    pub slotStart: xTime_t,
+    pub slotDuration: xTimeSpan_t,
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct xVCpuStatus_t {
+    pub state: xVCpuState_t,
+    pub schedStatus: xVCpuSchedStatus_t,
+}
+
+pub type xHmEvent_t = u32;
+pub const xHmApplicationError: xHmEvent_t = 0;
+pub const xHmNumericError: xHmEvent_t = 1;
+pub const xHmDeadlineMissed: xHmEvent_t = 2;
+pub const xHmIllegalRequest: xHmEvent_t = 3;
+pub const xHmStackOverflow: xHmEvent_t = 4;
+pub const xHmMemoryViolation: xHmEvent_t = 5;
+pub const xMaxHmMessageLength: u32 = 128;
+
+pub type xTraceCode_t = u32;
+pub const xMaxTracePayloadLength: u32 = 64;
+
+pub const xMaxConsoleWriteLength: u32 = 128;
+
+pub type xClockId_t = u32;
+pub const xHwClock: xClockId_t = 0;
+pub const xExecClock: xClockId_t = 1;
+pub const xInfiniteTime: xTime_t = -1;
+
+pub type xVIrq_t = u32;
+pub const xNoVIrqs: u32 = 32;
+pub const xVIrqHwTimer: xVIrq_t = 0;
+pub const xVIrqExecTimer: xVIrq_t = 1;
+pub type xIrqHandler_t = Option<unsafe extern "C" fn(irq: xVIrq_t)>;
+
+pub type xMessageSize_t = u32;
+pub type xMessageRange_t = u32;
+
+pub type xPortDirection_t = u32;
+pub const xSourcePort: xPortDirection_t = 0;
+pub const xDestinationPort: xPortDirection_t = 1;
+
+pub type xValidity_t = u32;
+pub const xInvalidMessage: xValidity_t = 0;
+pub const xValidMessage: xValidity_t = 1;
+
+pub type xSamplingPortId_t = i32;
+
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct xSamplingPortStatus_t {
+    pub refreshPeriod: xTime_t,
+    pub lastMessageTimestamp: xTime_t,
+    pub lastMessageSize: xMessageSize_t,
+    pub lastMessageValidity: xValidity_t,
+}
+
+pub type xQueuingPortId_t = i32;
+
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct xQueuingPortStatus_t {
+    pub maxMessageSize: xMessageSize_t,
+    pub maxNoMessages: xMessageRange_t,
+    pub noMessages: xMessageRange_t,
+    pub portDirection: xPortDirection_t,
+}
+
+pub unsafe fn XGetSystemTime(time: *mut xTime_t) -> xReturnCode_t {
+    *time = with_state(|state, _| state.now);
+    xNoError
+}
+
+pub unsafe fn XGetMyPartitionId(id: *mut xPartitionId_t) -> xReturnCode_t {
+    *id = with_state(|_, me| me);
+    xNoError
+}
+
+pub unsafe fn XGetPartitionId(name: *mut c_char, id: *mut xPartitionId_t) -> xReturnCode_t {
+    let name = CStr::from_ptr(name).to_string_lossy();
+    match with_state(|state, _| {
+        state
+            .partitions
+            .iter()
+            .find(|p| p.name == name)
+            .map(|p| p.id)
+    }) {
+        Some(found) => {
+            *id = found;
+            xNoError
+        }
+        None => xInvalidParam,
+    }
+}
+
+pub unsafe fn XHaltPartition(id: xPartitionId_t) -> xReturnCode_t {
+    match super::halt(id) {
+        Ok(()) => xNoError,
+        Err(code) => code,
+    }
+}
+
+pub unsafe fn XResetPartition(
+    id: xPartitionId_t,
+    mode: xResetMode_t,
+    _status: u32,
+) -> xReturnCode_t {
+    if mode != xWarmReset && mode != xColdReset {
+        return xInvalidParam;
+    }
+    match super::reset_partition(id) {
+        Ok(()) => xNoError,
+        Err(code) => code,
+    }
+}
+
+pub unsafe fn XGetVCpuStatus(id: xVCpuId_t, status: *mut xVCpuStatus_t) -> xReturnCode_t {
+    // every simulated partition runs on a single vCPU
+    if id != 0 {
+        return xInvalidParam;
+    }
+    *status = xVCpuStatus_t {
+        state: xVCpuRunning,
+        schedStatus: with_state(|state, _| state.current_slot()),
+    };
+    xNoError
+}
+
+pub unsafe fn XWaitUntilNextScheduleSlot() -> xReturnCode_t {
+    super::wait_until_next_slot();
+    xNoError
+}
+
+pub unsafe fn XGetScheduleStatus(status: *mut xScheduleStatus_t) -> xReturnCode_t {
+    *status = with_state(|state, _| xScheduleStatus_t {
+        currentPlanId: state.plan,
+        nextPlanId: state.next_plan,
+        lastPlanSwitchTime: state.last_plan_switch.unwrap_or(xInfiniteTime),
+    });
+    xNoError
+}
+
+pub unsafe fn XSwitchSchedulingPlan(plan: xcfPlanId_t) -> xReturnCode_t {
+    with_state(|state, _| {
+        if !state.plans.is_empty() && !state.plans.contains(&plan) {
+            return xInvalidParam;
+        }
+        if state.next_plan == plan {
+            return xNoAction;
+        }
+        state.next_plan = plan;
+        xNoError
+    })
+}
+
+pub unsafe fn XReportHmEvent(
+    event: xHmEvent_t,
+    _subcode: u32,
+    message: *mut c_char,
+    length: u32,
+) -> xReturnCode_t {
+    if event > xHmMemoryViolation || length > xMaxHmMessageLength {
+        return xInvalidParam;
+    }
+    let message = slice::from_raw_parts(message as *const u8, length as usize).to_vec();
+    with_state(|state, me| state.hm_events.push((me, event, message)));
+    xNoError
+}
+
+pub unsafe fn XTraceEvent(code: xTraceCode_t, payload: *mut c_void, length: u32) -> xReturnCode_t {
+    if length > xMaxTracePayloadLength {
+        return xInvalidParam;
+    }
+    let payload = slice::from_raw_parts(payload as *const u8, length as usize).to_vec();
+    with_state(|state, me| state.trace_events.push((me, code, payload)));
+    xNoError
+}
+
+pub unsafe fn XWriteConsole(buffer: *mut c_char, length: u32) -> xReturnCode_t {
+    if length > xMaxConsoleWriteLength {
+        return xInvalidParam;
+    }
+    let text = slice::from_raw_parts(buffer as *const u8, length as usize);
+    with_state(|state, _| state.console.extend_from_slice(text));
+    xNoError
+}
+
+pub unsafe fn XGetExecClock(time: *mut xTime_t) -> xReturnCode_t {
+    *time = with_state(|state, me| state.clock(xExecClock, me));
+    xNoError
+}
+
+pub unsafe fn XSetTimer(
+    clock: xClockId_t,
+    abs_time: xTime_t,
+    interval: xTimeSpan_t,
+) -> xReturnCode_t {
+    if clock > xExecClock || abs_time < 0 || interval < 0 {
+        return xInvalidParam;
+    }
+    with_state(|state, me| {
+        let timer = Timer {
+            expiration: abs_time,
+            interval,
+        };
+        state.timers.insert((me, clock), timer);
+    });
+    xNoError
+}
+
+pub unsafe fn XCancelTimer(clock: xClockId_t) -> xReturnCode_t {
+    if clock > xExecClock {
+        return xInvalidParam;
+    }
+    match with_state(|state, me| state.timers.remove(&(me, clock))) {
+        Some(_) => xNoError,
+        None => xNoAction,
+    }
+}
+
+pub unsafe fn XGetTimer(
+    clock: xClockId_t,
+    abs_time: *mut xTime_t,
+    interval: *mut xTimeSpan_t,
+) -> xReturnCode_t {
+    if clock > xExecClock {
+        return xInvalidParam;
+    }
+    let timer = with_state(|state, me| {
+        state.expire_timers();
+        state
+            .timers
+            .get(&(me, clock))
+            .map(|t| (t.expiration, t.interval))
+    });
+    (*abs_time, *interval) = timer.unwrap_or((xInfiniteTime, 0));
+    xNoError
+}
+
+pub unsafe fn XEnableIrqs() -> xReturnCode_t {
+    let enabled = with_state(|state, me| {
+        let virqs = state.virqs.entry(me).or_default();
+        std::mem::replace(&mut virqs.enabled, true)
+    });
+    if enabled {
+        return xNoAction;
+    }
+    super::deliver_virqs();
+    xNoError
+}
+
+pub unsafe fn XDisableIrqs() -> xReturnCode_t {
+    let enabled = with_state(|state, me| {
+        let virqs = state.virqs.entry(me).or_default();
+        std::mem::replace(&mut virqs.enabled, false)
+    });
+    if enabled {
+        xNoError
+    } else {
+        xNoAction
+    }
+}
+
+pub unsafe fn XMaskIrq(irq: xVIrq_t) -> xReturnCode_t {
+    if irq >= xNoVIrqs {
+        return xInvalidParam;
+    }
+    with_state(|state, me| state.virqs.entry(me).or_default().masked |= 1 << irq);
+    xNoError
+}
+
+pub unsafe fn XUnmaskIrq(irq: xVIrq_t) -> xReturnCode_t {
+    if irq >= xNoVIrqs {
+        return xInvalidParam;
+    }
+    with_state(|state, me| state.virqs.entry(me).or_default().masked &= !(1 << irq));
+    super::deliver_virqs();
+    xNoError
+}
+
+pub unsafe fn XAckIrq(irq: xVIrq_t) -> xReturnCode_t {
+    if irq >= xNoVIrqs {
+        return xInvalidParam;
+    }
+    with_state(|state, me| state.virqs.entry(me).or_default().pending &= !(1 << irq));
+    xNoError
+}
+
+pub unsafe fn XSetIrqHandler(irq: xVIrq_t, handler: xIrqHandler_t) -> xReturnCode_t {
+    if irq >= xNoVIrqs {
+        return xInvalidParam;
+    }
+    with_state(|state, me| state.virqs.entry(me).or_default().handlers[irq as usize] = handler);
+    xNoError
+}
+
+/// Register `port`, unless the partition already created a port of that name
+///
+/// Returns the id of the new port, or the return code to be yielded by the hypercall.
+fn find_or_create_port(
+    ports: &mut Vec<Port>,
+    port: Port,
+    compatible: impl FnOnce(&Port) -> bool,
+) -> Result<i32, xReturnCode_t> {
+    if let Some(existing) = ports
+        .iter()
+        .position(|p| p.partition == port.partition && p.name == port.name)
+    {
+        return if compatible(&ports[existing]) {
+            Err(xNoAction)
+        } else {
+            Err(xInvalidConfig)
+        };
+    }
+    ports.push(port);
+    Ok(ports.len() as i32 - 1)
+}
+
+fn check_direction(direction: xPortDirection_t) -> Result<(), xReturnCode_t> {
+    match direction {
+        xSourcePort | xDestinationPort => Ok(()),
+        _ => Err(xInvalidParam),
+    }
+}
+
+pub unsafe fn XCreateSamplingPort(
+    name: *mut c_char,
+    max_message_size: xMessageSize_t,
+    direction: xPortDirection_t,
+    refresh_period: xTime_t,
+    id: *mut xSamplingPortId_t,
+) -> xReturnCode_t {
+    let name = CStr::from_ptr(name).to_string_lossy().into_owned();
+    let max_message_size = max_message_size as usize;
+
+    let result = with_state(|state, me| {
+        check_direction(direction)?;
+        if refresh_period <= 0 || max_message_size == 0 {
+            return Err(xInvalidParam);
+        }
+
+        let channel = state.channel_name(&name);
+        let existing = state
+            .sampling_channels
+            .entry(channel.clone())
+            .or_insert_with(|| SamplingChannel {
+                max_message_size,
+                message: Vec::new(),
+                timestamp: None,
+            });
+        if existing.max_message_size != max_message_size {
+            return Err(xInvalidConfig);
+        }
+
+        let port = Port {
+            partition: me,
+            name,
+            channel,
+            direction,
+            max_message_size,
+            refresh_period,
+        };
+        find_or_create_port(&mut state.sampling_ports, port, |p| {
+            p.direction == direction && p.max_message_size == max_message_size
+        })
+    });
+
+    match result {
+        Ok(new_id) => {
+            *id = new_id;
+            xNoError
+        }
+        Err(code) => code,
+    }
+}
+
+/// Get the port with the id `id`, if it belongs to the calling partition
+fn own_port(ports: &[Port], id: i32, me: xPartitionId_t) -> Result<&Port, xReturnCode_t> {
+    usize::try_from(id)
+        .ok()
+        .and_then(|id| ports.get(id))
+        .filter(|p| p.partition == me)
+        .ok_or(xInvalidParam)
+}
+
+fn is_valid(state: &State, timestamp: Option<xTime_t>, refresh_period: xTime_t) -> bool {
+    timestamp.is_some_and(|ts| state.now - ts <= refresh_period)
+}
+
+pub unsafe fn XWriteSamplingMessage(
+    id: xSamplingPortId_t,
+    message: *mut c_void,
+    length: xMessageSize_t,
+) -> xReturnCode_t {
+    with_state(|state, me| {
+        let port = match own_port(&state.sampling_ports, id, me) {
+            Ok(port) => port,
+            Err(code) => return code,
+        };
+        if port.direction != xSourcePort {
+            return xInvalidMode;
+        }
+        let length = length as usize;
+        if length == 0 || length > port.max_message_size {
+            return xInvalidParam;
+        }
+
+        let now = state.now;
+        let channel = state.sampling_channels.get_mut(&port.channel).unwrap();
+        channel.message = slice::from_raw_parts(message as *const u8, length).to_vec();
+        channel.timestamp = Some(now);
+        xNoError
+    })
+}
+
+pub unsafe fn XReadSamplingMessage(
+    id: xSamplingPortId_t,
+    message: *mut c_void,
+    length: *mut xMessageSize_t,
+    validity: *mut xValidity_t,
+) -> xReturnCode_t {
+    with_state(|state, me| {
+        let port = match own_port(&state.sampling_ports, id, me) {
+            Ok(port) => port,
+            Err(code) => return code,
+        };
+        if port.direction != xDestinationPort {
+            return xInvalidMode;
+        }
+
+        let channel = &state.sampling_channels[&port.channel];
+        if channel.timestamp.is_none() {
+            return xNotAvailable;
+        }
+        let valid = is_valid(state, channel.timestamp, port.refresh_period);
+
+        std::ptr::copy_nonoverlapping(
+            channel.message.as_ptr(),
+            message as *mut u8,
+            channel.message.len(),
+        );
+        *length = channel.message.len() as xMessageSize_t;
+        *validity = if valid {
+            xValidMessage
+        } else {
+            xInvalidMessage
+        };
+        xNoError
+    })
+}
+
+pub unsafe fn XGetSamplingPortStatus(
+    id: xSamplingPortId_t,
+    status: *mut xSamplingPortStatus_t,
+) -> xReturnCode_t {
+    with_state(|state, me| {
+        let port = match own_port(&state.sampling_ports, id, me) {
+            Ok(port) => port,
+            Err(code) => return code,
+        };
+
+        let channel = &state.sampling_channels[&port.channel];
+        let valid = is_valid(state, channel.timestamp, port.refresh_period);
+        *status = xSamplingPortStatus_t {
+            refreshPeriod: port.refresh_period,
+            lastMessageTimestamp: channel.timestamp.unwrap_or(-1),
+            lastMessageSize: channel.message.len() as xMessageSize_t,
+            lastMessageValidity: if valid {
+                xValidMessage
+            } else {
+                xInvalidMessage
+            },
+        };
+        xNoError
+    })
+}
+
+pub unsafe fn XCreateQueuingPort(
+    name: *mut c_char,
+    max_message_size: xMessageSize_t,
+    max_messages: xMessageRange_t,
+    direction: xPortDirection_t,
+    id: *mut xQueuingPortId_t,
+) -> xReturnCode_t {
+    let name = CStr::from_ptr(name).to_string_lossy().into_owned();
+    let max_message_size = max_message_size as usize;
+    let max_messages = max_messages as usize;
+
+    let result = with_state(|state, me| {
+        check_direction(direction)?;
+        if max_message_size == 0 || max_messages == 0 {
+            return Err(xInvalidParam);
+        }
+
+        let channel = state.channel_name(&name);
+        let existing = state
+            .queuing_channels
+            .entry(channel.clone())
+            .or_insert_with(|| QueuingChannel {
+                max_message_size,
+                max_messages,
+                messages: VecDeque::new(),
+            });
+        if existing.max_message_size != max_message_size || existing.max_messages != max_messages {
+            return Err(xInvalidConfig);
+        }
+
+        let port = Port {
+            partition: me,
+            name,
+            channel,
+            direction,
+            max_message_size,
+            refresh_period: 0,
+        };
+        find_or_create_port(&mut state.queuing_ports, port, |p| {
+            p.direction == direction && p.max_message_size == max_message_size
+        })
+    });
+
+    match result {
+        Ok(new_id) => {
+            *id = new_id;
+            xNoError
+        }
+        Err(code) => code,
+    }
+}
+
+pub unsafe fn XSendQueuingMessage(
+    id: xQueuingPortId_t,
+    message: *mut c_void,
+    length: xMessageSize_t,
+) -> xReturnCode_t {
+    with_state(|state, me| {
+        let port = match own_port(&state.queuing_ports, id, me) {
+            Ok(port) => port,
+            Err(code) => return code,
+        };
+        if port.direction != xSourcePort {
+            return xInvalidMode;
+        }
+        let length = length as usize;
+        if length == 0 || length > port.max_message_size {
+            return xInvalidParam;
+        }
+
+        let channel = state.queuing_channels.get_mut(&port.channel).unwrap();
+        if channel.messages.len() >= channel.max_messages {
+            return xNotAvailable;
+        }
+        let message = slice::from_raw_parts(message as *const u8, length).to_vec();
+        channel.messages.push_back(message);
+        xNoError
+    })
+}
+
+pub unsafe fn XReceiveQueuingMessage(
+    id: xQueuingPortId_t,
+    message: *mut c_void,
+    length: *mut xMessageSize_t,
+) -> xReturnCode_t {
+    with_state(|state, me| {
+        let port = match own_port(&state.queuing_ports, id, me) {
+            Ok(port) => port,
+            Err(code) => return code,
+        };
+        if port.direction != xDestinationPort {
+            return xInvalidMode;
+        }
+
+        let channel = state.queuing_channels.get_mut(&port.channel).unwrap();
+        let received = match channel.messages.pop_front() {
+            Some(received) => received,
+            None => return xNotAvailable,
+        };
+        std::ptr::copy_nonoverlapping(received.as_ptr(), message as *mut u8, received.len());
+        *length = received.len() as xMessageSize_t;
+        xNoError
+    })
+}
+
+pub unsafe fn XGetQueuingPortStatus(
+    id: xQueuingPortId_t,
+    status: *mut xQueuingPortStatus_t,
+) -> xReturnCode_t {
+    with_state(|state, me| {
+        let port = match own_port(&state.queuing_ports, id, me) {
+            Ok(port) => port,
+            Err(code) => return code,
+        };
+
+        let channel = &state.queuing_channels[&port.channel];
+        *status = xQueuingPortStatus_t {
+            maxMessageSize: channel.max_message_size as xMessageSize_t,
+            maxNoMessages: channel.max_messages as xMessageRange_t,
+            noMessages: channel.messages.len() as xMessageRange_t,
+            portDirection: port.direction,
+        };
+        xNoError
+    })
+}
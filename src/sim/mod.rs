@@ -0,0 +1,677 @@
+//! A pure Rust simulation of the XNG hypercalls
+//!
+//! With the `sim` feature enabled, the [`bindings`](crate::bindings) module does not link against
+//! XNG/XRE but is implemented in Rust, so that partitions and this crate itself can be run and
+//! tested on a host. This module contains the functions to set up and inspect the simulated
+//! system.
+//!
+//! Every thread has its own simulated system, which is created on first use. Thus tests running
+//! in parallel do not interfere with each other. The simulated system time does only advance when
+//! told so, either via [`advance`] or by waiting for the next slot via
+//! [`crate::vcpu::wait_until_next_schedule_slot`].
+//!
+//! Ports are connected to the port with the same name, unless a different routing is configured
+//! via [`connect`].
+//!
+//! To run several partitions under a cyclic schedule, use the [`Simulator`].
+//!
+//! # Examples
+//!
+//! ```
+//! # fn main() -> Result<(), xng_rs::XngError> {
+//! use xng_rs::prelude::*;
+//! use xng_rs::sim;
+//! use xng_rs::time::DurationFromInt;
+//!
+//! sim::set_partition("Sensor", 1);
+//! sim::connect("altitude_out", "altitude_in");
+//!
+//! let sender = port::SamplingSender::<4>::new(cstr!("altitude_out"))?;
+//! let receiver = port::SamplingReceiver::<4>::new(cstr!("altitude_in"), 10u32.ms())?;
+//!
+//! sender.send(&[1, 2, 3, 4])?;
+//! let mut buf = [0; 4];
+//! assert_eq!(receiver.recv(&mut buf)?, Some((&mut [1, 2, 3, 4][..], true)));
+//!
+//! // after the time to live, the message is no longer valid
+//! sim::advance(20u32.ms());
+//! assert_eq!(receiver.recv(&mut buf)?, Some((&mut [1, 2, 3, 4][..], false)));
+//! # Ok(())}
+//! ```
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use crate::{partition::PartitionId, time::Duration};
+
+pub(crate) mod bindings;
+mod schedule;
+
+use bindings::{
+    xClockId_t, xIrqHandler_t, xPortDirection_t, xTimeSpan_t, xTime_t, xVCpuSchedStatus_t, xVIrq_t,
+    xcfPlanId_t,
+};
+pub use schedule::Simulator;
+
+/// A simulated system, shared by all partitions running in it
+#[derive(Default)]
+pub(crate) struct World {
+    state: Mutex<State>,
+    baton: schedule::Baton,
+}
+
+impl World {
+    pub(crate) fn lock(&self) -> MutexGuard<'_, State> {
+        // a panicking partition must not take down the whole simulation
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// The state of a simulated system
+pub(crate) struct State {
+    /// The system time in microseconds
+    pub now: xTime_t,
+    /// The period of the schedule slots in microseconds
+    pub slot_period: xTime_t,
+    pub partitions: Vec<Partition>,
+    /// Maps the name of a destination port to the name of its source port
+    pub routes: HashMap<String, String>,
+    pub sampling_channels: HashMap<String, SamplingChannel>,
+    pub sampling_ports: Vec<Port>,
+    pub queuing_channels: HashMap<String, QueuingChannel>,
+    pub queuing_ports: Vec<Port>,
+    /// The events reported to the health monitor
+    pub hm_events: Vec<(PartitionId, bindings::xHmEvent_t, Vec<u8>)>,
+    /// The recorded trace events
+    pub trace_events: Vec<(PartitionId, bindings::xTraceCode_t, Vec<u8>)>,
+    /// The text written to the console
+    pub console: Vec<u8>,
+    /// The execution time consumed by each partition in microseconds
+    pub exec_times: HashMap<PartitionId, xTime_t>,
+    /// The armed virtual timers by partition and clock
+    pub timers: HashMap<(PartitionId, xClockId_t), Timer>,
+    /// The virtual interrupts of each partition
+    pub virqs: HashMap<PartitionId, VIrqs>,
+    /// The scheduling plan in effect
+    pub plan: xcfPlanId_t,
+    /// The scheduling plan in effect after the current major frame
+    pub next_plan: xcfPlanId_t,
+    /// When the current plan came into effect, `None` if it never changed
+    pub last_plan_switch: Option<xTime_t>,
+    /// The known scheduling plans, any plan is accepted if empty
+    pub plans: Vec<xcfPlanId_t>,
+    /// The slot run by a `Simulator`, `None` without one
+    pub slot: Option<xVCpuSchedStatus_t>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            now: 0,
+            slot_period: 1_000,
+            partitions: Vec::new(),
+            routes: HashMap::new(),
+            sampling_channels: HashMap::new(),
+            sampling_ports: Vec::new(),
+            queuing_channels: HashMap::new(),
+            queuing_ports: Vec::new(),
+            hm_events: Vec::new(),
+            trace_events: Vec::new(),
+            console: Vec::new(),
+            exec_times: HashMap::new(),
+            timers: HashMap::new(),
+            virqs: HashMap::new(),
+            plan: 0,
+            next_plan: 0,
+            last_plan_switch: None,
+            plans: Vec::new(),
+            slot: None,
+        }
+    }
+}
+
+impl State {
+    /// Get the name of the channel the port `port_name` belongs to
+    pub fn channel_name(&self, port_name: &str) -> String {
+        self.routes
+            .get(port_name)
+            .cloned()
+            .unwrap_or_else(|| port_name.to_owned())
+    }
+
+    /// Register a partition, or update the name of an already registered one
+    pub fn add_partition(&mut self, name: &str, id: PartitionId) {
+        match self.partitions.iter_mut().find(|p| p.id == id) {
+            Some(p) => p.name = name.to_owned(),
+            None => self.partitions.push(Partition {
+                name: name.to_owned(),
+                id,
+                halted: false,
+                restarts: 0,
+                finished: false,
+            }),
+        }
+    }
+
+    /// Mark the partition `id` as finished, it will not be scheduled anymore
+    pub fn finish(&mut self, id: PartitionId) {
+        if let Some(p) = self.partitions.iter_mut().find(|p| p.id == id) {
+            p.finished = true;
+        }
+    }
+
+    /// Get the current value of the clock `clock` of the partition `id` in microseconds
+    pub fn clock(&self, clock: xClockId_t, id: PartitionId) -> xTime_t {
+        match clock {
+            bindings::xExecClock => self.exec_times.get(&id).copied().unwrap_or_default(),
+            _ => self.now,
+        }
+    }
+
+    /// Rearm the expired periodic timers and disarm the expired one-shot timers
+    ///
+    /// The vIRQ of every expired timer becomes pending.
+    pub fn expire_timers(&mut self) {
+        let clocks: Vec<_> = self
+            .timers
+            .keys()
+            .map(|&(id, clock)| ((id, clock), self.clock(clock, id)))
+            .collect();
+        for (key, now) in clocks {
+            let timer = self.timers.get_mut(&key).unwrap();
+            if timer.expiration > now {
+                continue;
+            }
+            let (id, clock) = key;
+            let irq = match clock {
+                bindings::xExecClock => bindings::xVIrqExecTimer,
+                _ => bindings::xVIrqHwTimer,
+            };
+            self.virqs.entry(id).or_default().pending |= 1 << irq;
+
+            let timer = self.timers.get_mut(&key).unwrap();
+            if timer.interval > 0 {
+                let missed = (now - timer.expiration) / timer.interval + 1;
+                timer.expiration += missed * timer.interval;
+            } else {
+                self.timers.remove(&key);
+            }
+        }
+    }
+
+    /// Start a major frame, switching to the next plan if requested
+    pub fn start_major_frame(&mut self) -> xcfPlanId_t {
+        if self.next_plan != self.plan {
+            self.plan = self.next_plan;
+            self.last_plan_switch = Some(self.now);
+        }
+        self.plan
+    }
+
+    /// Get the status of the current slot
+    ///
+    /// Without a `Simulator`, slot 0 repeats every `slot_period`.
+    pub fn current_slot(&self) -> xVCpuSchedStatus_t {
+        self.slot.unwrap_or(xVCpuSchedStatus_t {
+            slotId: 0,
+            slotStart: self.now - self.now % self.slot_period,
+            slotDuration: self.slot_period,
+        })
+    }
+
+    /// Take the next vIRQ to be delivered to the partition `id`, together with its handler
+    ///
+    /// The vIRQ is no longer pending and vIRQs are disabled until the handler returns.
+    pub fn next_virq(&mut self, id: PartitionId) -> Option<(xVIrq_t, xIrqHandler_t)> {
+        let virqs = self.virqs.entry(id).or_default();
+        let deliverable = virqs.pending & !virqs.masked;
+        if !virqs.enabled || deliverable == 0 {
+            return None;
+        }
+        let irq = deliverable.trailing_zeros();
+        virqs.pending &= !(1 << irq);
+        virqs.enabled = false;
+        Some((irq, virqs.handlers[irq as usize]))
+    }
+
+    /// Whether the partition `id` can be scheduled
+    pub fn is_runnable(&self, id: PartitionId) -> bool {
+        self.partitions
+            .iter()
+            .any(|p| p.id == id && !p.halted && !p.finished)
+    }
+}
+
+/// A simulated partition
+pub(crate) struct Partition {
+    pub name: String,
+    pub id: PartitionId,
+    pub halted: bool,
+    /// Times the partition was restarted
+    pub restarts: u32,
+    /// Whether the main function of the partition returned
+    pub finished: bool,
+}
+
+/// One end of a simulated port
+pub(crate) struct Port {
+    pub partition: PartitionId,
+    pub name: String,
+    pub channel: String,
+    pub direction: xPortDirection_t,
+    pub max_message_size: usize,
+    /// The refresh period of a sampling port in microseconds
+    pub refresh_period: xTime_t,
+}
+
+/// An armed virtual timer
+pub(crate) struct Timer {
+    /// The next expiration in microseconds of the timer's clock
+    pub expiration: xTime_t,
+    /// The period in microseconds, zero for a one-shot timer
+    pub interval: xTimeSpan_t,
+}
+
+/// The virtual interrupts of a partition
+pub(crate) struct VIrqs {
+    /// Whether vIRQs are delivered at all
+    pub enabled: bool,
+    /// One bit per masked vIRQ
+    pub masked: u32,
+    /// One bit per pending vIRQ
+    pub pending: u32,
+    pub handlers: [xIrqHandler_t; bindings::xNoVIrqs as usize],
+    /// The Rust handlers the `virq` module dispatches to
+    ///
+    /// They are kept per partition, as all simulated partitions share the statics of the crate.
+    #[cfg(feature = "virq")]
+    pub rust_handlers: [Option<fn(xVIrq_t)>; bindings::xNoVIrqs as usize],
+}
+
+impl Default for VIrqs {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            masked: u32::MAX,
+            pending: 0,
+            handlers: [None; bindings::xNoVIrqs as usize],
+            #[cfg(feature = "virq")]
+            rust_handlers: [None; bindings::xNoVIrqs as usize],
+        }
+    }
+}
+
+/// The channel connecting sampling ports
+pub(crate) struct SamplingChannel {
+    pub max_message_size: usize,
+    pub message: Vec<u8>,
+    /// Timestamp of the last message, `None` if it was never written
+    pub timestamp: Option<xTime_t>,
+}
+
+/// The channel connecting queuing ports
+pub(crate) struct QueuingChannel {
+    pub max_message_size: usize,
+    pub max_messages: usize,
+    pub messages: VecDeque<Vec<u8>>,
+}
+
+struct Context {
+    world: Arc<World>,
+    partition: PartitionId,
+}
+
+thread_local! {
+    /// The simulated system and partition the current thread belongs to
+    static CONTEXT: RefCell<Context> = RefCell::new(Context {
+        world: Arc::default(),
+        partition: 0,
+    });
+}
+
+/// Get the simulated system and partition of the current thread
+fn context() -> (Arc<World>, PartitionId) {
+    CONTEXT.with(|c| {
+        let c = c.borrow();
+        (c.world.clone(), c.partition)
+    })
+}
+
+/// Let the current thread act as the partition `partition` of `world`
+fn enter(world: Arc<World>, partition: PartitionId) {
+    CONTEXT.with(|c| *c.borrow_mut() = Context { world, partition });
+}
+
+/// Run `f` on the state of the simulated system of the current thread
+pub(crate) fn with_state<R>(f: impl FnOnce(&mut State, PartitionId) -> R) -> R {
+    let (world, partition) = context();
+    let mut state = world.lock();
+    f(&mut state, partition)
+}
+
+/// Yield the CPU until the next slot of the calling partition
+///
+/// Without a [`Simulator`], the system time is advanced to the start of the next slot.
+pub(crate) fn wait_until_next_slot() {
+    let (world, me) = context();
+    if world.baton.is_scheduled() {
+        world.baton.yield_slot(me);
+    } else {
+        // without a `Simulator`, every slot is a major frame of its own
+        let mut state = world.lock();
+        state.now = (state.now / state.slot_period + 1) * state.slot_period;
+        state.start_major_frame();
+    }
+    deliver_virqs();
+}
+
+/// Call the handlers of the vIRQs deliverable to the calling partition
+pub(crate) fn deliver_virqs() {
+    while let Some((irq, handler)) = with_state(|state, me| {
+        state.expire_timers();
+        state.next_virq(me)
+    }) {
+        if let Some(handler) = handler {
+            unsafe { handler(irq) };
+        }
+        with_state(|state, me| state.virqs.entry(me).or_default().enabled = true);
+    }
+}
+
+/// Set the Rust handler of the vIRQ `irq` of the calling partition
+///
+/// Returns `false` if there is no such vIRQ.
+#[cfg(feature = "virq")]
+pub(crate) fn set_rust_handler(irq: xVIrq_t, handler: Option<fn(xVIrq_t)>) -> bool {
+    with_state(|state, me| {
+        let virqs = state.virqs.entry(me).or_default();
+        match virqs.rust_handlers.get_mut(irq as usize) {
+            Some(slot) => {
+                *slot = handler;
+                true
+            }
+            None => false,
+        }
+    })
+}
+
+/// Get the Rust handler of the vIRQ `irq` of the calling partition
+#[cfg(feature = "virq")]
+pub(crate) fn rust_handler(irq: xVIrq_t) -> Option<fn(xVIrq_t)> {
+    with_state(|state, me| {
+        let virqs = state.virqs.entry(me).or_default();
+        virqs.rust_handlers.get(irq as usize).copied().flatten()
+    })
+}
+
+/// Halt the partition `id`
+///
+/// If the calling partition halts itself under a [`Simulator`], it does not continue to run.
+pub(crate) fn halt(id: PartitionId) -> Result<(), bindings::xReturnCode_t> {
+    let (world, me) = context();
+    {
+        let mut state = world.lock();
+        match state.partitions.iter_mut().find(|p| p.id == id) {
+            Some(p) if p.halted => return Err(bindings::xNoAction),
+            Some(p) => p.halted = true,
+            None => return Err(bindings::xInvalidParam),
+        }
+    }
+    if id == me && world.baton.is_scheduled() {
+        world.baton.release(me);
+        schedule::stop_partition();
+    }
+    Ok(())
+}
+
+/// Restart the partition `id`
+///
+/// The simulation can not rerun the main function of a partition. A partition restarting itself
+/// under a [`Simulator`] therefore stops to run, like a halted one.
+pub(crate) fn reset_partition(id: PartitionId) -> Result<(), bindings::xReturnCode_t> {
+    let (world, me) = context();
+    {
+        let mut state = world.lock();
+        match state.partitions.iter_mut().find(|p| p.id == id) {
+            Some(p) => p.restarts += 1,
+            None => return Err(bindings::xInvalidParam),
+        }
+        state.timers.retain(|&(partition, _), _| partition != id);
+        state.virqs.remove(&id);
+    }
+    if id == me && world.baton.is_scheduled() {
+        world.lock().finish(me);
+        world.baton.release(me);
+        schedule::stop_partition();
+    }
+    Ok(())
+}
+
+/// Let the current thread act as the partition `name` with the id `id`
+pub fn set_partition(name: &str, id: PartitionId) {
+    CONTEXT.with(|c| c.borrow_mut().partition = id);
+    add_partition(name, id);
+}
+
+/// Make a partition known to the simulated system, without acting as it
+pub fn add_partition(name: &str, id: PartitionId) {
+    with_state(|state, _| state.add_partition(name, id));
+}
+
+/// Connect the destination port `destination` to the source port `source`
+///
+/// Must be called before the ports are created.
+pub fn connect(source: &str, destination: &str) {
+    with_state(|state, _| {
+        state
+            .routes
+            .insert(destination.to_owned(), source.to_owned())
+    });
+}
+
+/// Advance the simulated system time by `duration`
+///
+/// The time is accounted as execution time of the calling partition, waiting for the next slot is
+/// not.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), xng_rs::XngError> {
+/// use xng_rs::sim;
+/// use xng_rs::time::{DurationFromInt, ExecutionClock};
+///
+/// let (_, consumed) = ExecutionClock::measure(|| sim::advance(3u32.ms()))?;
+/// assert_eq!(consumed, 3u32.ms());
+///
+/// xng_rs::vcpu::wait_until_next_schedule_slot();
+/// assert_eq!(ExecutionClock::now()?, 3u32.ms());
+/// # Ok(())}
+/// ```
+pub fn advance(duration: Duration) {
+    let duration = duration.as_micros() as xTime_t;
+    with_state(|state, me| {
+        state.now += duration;
+        *state.exec_times.entry(me).or_default() += duration;
+    });
+    deliver_virqs();
+}
+
+/// Raise the vIRQ `irq` of the partition with the id `id`
+///
+/// If the calling partition raises one of its own vIRQs, it is delivered immediately, provided it
+/// is unmasked and vIRQs are enabled.
+///
+/// # Examples
+///
+/// ```
+/// # fn main() -> Result<(), xng_rs::XngError> {
+/// use core::sync::atomic::{AtomicBool, Ordering};
+/// use xng_rs::{sim, virq};
+///
+/// static CALLED: AtomicBool = AtomicBool::new(false);
+///
+/// virq::set_handler(5, |_| CALLED.store(true, Ordering::Relaxed))?;
+/// sim::raise_virq(0, 5);
+/// assert!(!CALLED.load(Ordering::Relaxed));
+///
+/// virq::unmask(5)?;
+/// virq::enable()?;
+/// assert!(CALLED.load(Ordering::Relaxed));
+/// # Ok(())}
+/// ```
+pub fn raise_virq(id: PartitionId, irq: xVIrq_t) {
+    with_state(|state, _| state.virqs.entry(id).or_default().pending |= 1 << irq);
+    if id == context().1 {
+        deliver_virqs();
+    }
+}
+
+/// Set the period of the schedule slots
+///
+/// Waiting for the next slot advances the system time to the next multiple of `period`.
+pub fn set_slot_period(period: Duration) {
+    with_state(|state, _| state.slot_period = (period.as_micros() as xTime_t).max(1));
+}
+
+/// How often the partition with the id `id` was restarted
+pub fn restarts(id: PartitionId) -> u32 {
+    Report::current().restarts(id)
+}
+
+/// Whether the partition with the id `id` was halted
+pub fn is_halted(id: PartitionId) -> bool {
+    Report::current().is_halted(id)
+}
+
+/// Get all events reported to the health monitor so far, together with the reporting partition
+///
+/// # Examples
+///
+/// ```
+/// use xng_rs::{hm::{self, HmEvent}, sim};
+///
+/// hm::report(HmEvent::DeadlineMissed, &[b'x'; 200]).unwrap();
+///
+/// let events = sim::hm_events();
+/// assert_eq!(events[0].1, HmEvent::DeadlineMissed);
+/// assert_eq!(events[0].2.len(), hm::MAX_MESSAGE_LENGTH);
+/// ```
+#[cfg(feature = "hm")]
+pub fn hm_events() -> Vec<(PartitionId, crate::hm::HmEvent, Vec<u8>)> {
+    Report::current().hm_events()
+}
+
+/// Get all trace events recorded so far, together with the recording partition
+///
+/// # Examples
+///
+/// ```
+/// use xng_rs::{sim, trace};
+///
+/// trace::event_fmt(7, format_args!("altitude {}", 1337)).unwrap();
+///
+/// let events = sim::trace_events();
+/// assert_eq!(events[0].1, 7);
+/// assert_eq!(events[0].2, b"altitude 1337");
+/// ```
+#[cfg(feature = "trace")]
+pub fn trace_events() -> Vec<(PartitionId, crate::trace::TraceCode, Vec<u8>)> {
+    Report::current().trace_events()
+}
+
+/// Get the text written to the console so far
+///
+/// # Examples
+///
+/// ```
+/// use xng_rs::sim;
+///
+/// xng_rs::println!("altitude {}", 1337);
+///
+/// assert_eq!(sim::console_output(), "altitude 1337\n");
+/// ```
+#[cfg(feature = "legacy")]
+pub fn console_output() -> String {
+    Report::current().console_output()
+}
+
+/// The observable outcome of a simulated system
+///
+/// Returned by [`Simulator::run`], so that the outcome of the partitions can be checked after the
+/// simulation ended. The functions of the same name in this module report on the simulated system
+/// of the current thread instead.
+pub struct Report {
+    world: Arc<World>,
+}
+
+impl Report {
+    /// The report on the simulated system of the current thread
+    fn current() -> Self {
+        Self { world: context().0 }
+    }
+
+    /// How often the partition with the id `id` was restarted
+    pub fn restarts(&self, id: PartitionId) -> u32 {
+        self.world
+            .lock()
+            .partitions
+            .iter()
+            .find(|p| p.id == id)
+            .map_or(0, |p| p.restarts)
+    }
+
+    /// Whether the partition with the id `id` was halted
+    pub fn is_halted(&self, id: PartitionId) -> bool {
+        let state = self.world.lock();
+        state.partitions.iter().any(|p| p.id == id && p.halted)
+    }
+
+    /// Get all events reported to the health monitor, together with the reporting partition
+    #[cfg(feature = "hm")]
+    pub fn hm_events(&self) -> Vec<(PartitionId, crate::hm::HmEvent, Vec<u8>)> {
+        use crate::hm::HmEvent;
+
+        self.world
+            .lock()
+            .hm_events
+            .iter()
+            .map(|(partition, event, message)| {
+                let event = match *event {
+                    bindings::xHmApplicationError => HmEvent::ApplicationError,
+                    bindings::xHmNumericError => HmEvent::NumericError,
+                    bindings::xHmDeadlineMissed => HmEvent::DeadlineMissed,
+                    bindings::xHmIllegalRequest => HmEvent::IllegalRequest,
+                    bindings::xHmStackOverflow => HmEvent::StackOverflow,
+                    _ => HmEvent::MemoryViolation,
+                };
+                (*partition, event, message.clone())
+            })
+            .collect()
+    }
+
+    /// Get all recorded trace events, together with the recording partition
+    #[cfg(feature = "trace")]
+    pub fn trace_events(&self) -> Vec<(PartitionId, crate::trace::TraceCode, Vec<u8>)> {
+        self.world.lock().trace_events.clone()
+    }
+
+    /// Get the text written to the console
+    #[cfg(feature = "legacy")]
+    pub fn console_output(&self) -> String {
+        String::from_utf8_lossy(&self.world.lock().console).into_owned()
+    }
+}
+
+/// Replace the simulated system of the current thread by a fresh one
+pub fn reset() {
+    CONTEXT.with(|c| {
+        *c.borrow_mut() = Context {
+            world: Arc::default(),
+            partition: 0,
+        }
+    });
+}
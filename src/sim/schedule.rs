@@ -0,0 +1,368 @@
+use std::{
+    any::Any,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    thread::{self, JoinHandle},
+};
+
+use super::{
+    bindings::{xTimeSpan_t, xTime_t, xVCpuSchedStatus_t, xcfPlanId_t, xcfSlotId_t},
+    enter, Report, World,
+};
+use crate::{partition::PartitionId, time::Duration};
+
+/// Payload used to unwind partition threads when the simulation ends
+struct Stop;
+
+/// Payload of a panic
+type PanicPayload = Box<dyn Any + Send>;
+
+/// Decides which partition of a scheduled world may run
+#[derive(Default)]
+pub(crate) struct Baton {
+    state: Mutex<BatonState>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct BatonState {
+    /// Whether a `Simulator` schedules the partitions of this world
+    scheduled: bool,
+    /// The partition which currently owns the CPU
+    running: Option<PartitionId>,
+    /// Whether the simulation is over
+    stopping: bool,
+}
+
+impl Baton {
+    fn lock(&self) -> MutexGuard<'_, BatonState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn wait<'a>(&self, guard: MutexGuard<'a, BatonState>) -> MutexGuard<'a, BatonState> {
+        self.changed.wait(guard).unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn is_scheduled(&self) -> bool {
+        self.lock().scheduled
+    }
+
+    /// Block until the partition `me` is scheduled
+    ///
+    /// Unwinds the calling thread if the simulation ends in the meantime.
+    fn wait_for_turn(&self, me: PartitionId) {
+        let mut state = self.lock();
+        while state.running != Some(me) && !state.stopping {
+            state = self.wait(state);
+        }
+        if state.stopping {
+            drop(state);
+            panic::resume_unwind(Box::new(Stop));
+        }
+    }
+
+    /// Hand the CPU back to the scheduler and block until `me` is scheduled again
+    pub(crate) fn yield_slot(&self, me: PartitionId) {
+        self.release(me);
+        self.wait_for_turn(me);
+    }
+
+    /// Hand the CPU back to the scheduler
+    pub(crate) fn release(&self, me: PartitionId) {
+        let mut state = self.lock();
+        if state.running == Some(me) {
+            state.running = None;
+            self.changed.notify_all();
+        }
+    }
+
+    /// Let `partition` run until it hands back the CPU
+    fn run(&self, partition: PartitionId) {
+        let mut state = self.lock();
+        state.running = Some(partition);
+        self.changed.notify_all();
+        while state.running == Some(partition) {
+            state = self.wait(state);
+        }
+    }
+
+    fn stop(&self) {
+        let mut state = self.lock();
+        state.stopping = true;
+        self.changed.notify_all();
+    }
+}
+
+/// Unwind the calling partition thread, e.g. because it halted itself
+pub(crate) fn stop_partition() -> ! {
+    panic::resume_unwind(Box::new(Stop))
+}
+
+struct SimPartition {
+    name: String,
+    id: PartitionId,
+    entry: Box<dyn FnOnce() + Send>,
+}
+
+/// A host side simulation of a XNG system running several partitions
+///
+/// Every partition runs in its own thread, but only the partition owning the current slot of the
+/// cyclic schedule is executed. Waiting for the next slot via
+/// [`crate::vcpu::wait_until_next_schedule_slot`] hands control to the partition owning the next
+/// slot. The system time is set to the start of a slot whenever the slot begins, unless the
+/// previous partition overran its slot via [`crate::sim::advance`]. Then the slot starts late, as
+/// the system time never goes back.
+///
+/// Messages are routed between the partitions like XNG would route them: ports are connected to
+/// the port with the same name, unless a different routing is configured via
+/// [`Simulator::connect`].
+///
+/// If a partition panics, the panic is propagated from [`Simulator::run`] after the simulation
+/// ended.
+///
+/// # Examples
+///
+/// ```
+/// use xng_rs::prelude::*;
+/// use xng_rs::{sim::Simulator, time::DurationFromInt, vcpu};
+///
+/// Simulator::new()
+///     .partition("Sensor", 1, || {
+///         let port = port::QueuingSender::<4, 8>::new(cstr!("altitude")).unwrap();
+///         for altitude in 0u32.. {
+///             port.send(&altitude.to_le_bytes()).unwrap();
+///             vcpu::wait_until_next_schedule_slot();
+///         }
+///     })
+///     .partition("Display", 2, || {
+///         let port = port::QueuingReceiver::<4, 8>::new(cstr!("altitude")).unwrap();
+///         let mut buf = [0; 4];
+///         for expected in 0u32.. {
+///             let msg = port.recv(&mut buf).unwrap().unwrap();
+///             assert_eq!(msg, &expected.to_le_bytes());
+///             vcpu::wait_until_next_schedule_slot();
+///         }
+///     })
+///     .slot(1, 5u32.ms())
+///     .slot(2, 5u32.ms())
+///     .run(10);
+/// ```
+pub struct Simulator {
+    world: Arc<World>,
+    partitions: Vec<SimPartition>,
+    /// The slots of each scheduling plan, the first plan is in effect at boot
+    plans: Vec<(xcfPlanId_t, Vec<(PartitionId, Duration)>)>,
+}
+
+impl Default for Simulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Simulator {
+    /// Create a simulation without any partitions
+    pub fn new() -> Self {
+        let world = Arc::new(World::default());
+        world.baton.lock().scheduled = true;
+        Self {
+            world,
+            partitions: Vec::new(),
+            plans: vec![(0, Vec::new())],
+        }
+    }
+
+    /// Add the partition `name` with the id `id`, running `entry` as its main function
+    pub fn partition<F>(mut self, name: &str, id: PartitionId, entry: F) -> Self
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.world.lock().add_partition(name, id);
+        self.partitions.push(SimPartition {
+            name: name.to_owned(),
+            id,
+            entry: Box::new(entry),
+        });
+        self
+    }
+
+    /// Append a slot of `duration` owned by the partition `partition` to the major frame
+    ///
+    /// The slot is appended to the plan added last, or to plan 0 if none was added. The slots of a
+    /// plan are numbered in the order they are added, starting at 0.
+    pub fn slot(mut self, partition: PartitionId, duration: Duration) -> Self {
+        let (_, slots) = self.plans.last_mut().expect("there is always a plan");
+        slots.push((partition, duration));
+        self
+    }
+
+    /// Add the scheduling plan with the id `id`, the following slots belong to it
+    ///
+    /// Slots added before the first plan belong to plan 0, which is in effect at boot. If no slot
+    /// is added before, the plan added first is in effect at boot instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xng_rs::{schedule::{self, PlanId}, sim::Simulator, time::DurationFromInt, vcpu};
+    ///
+    /// Simulator::new()
+    ///     .partition("ModeManager", 1, || {
+    ///         assert_eq!(schedule::current_plan().unwrap(), PlanId(1));
+    ///         schedule::switch_plan(PlanId(2)).unwrap();
+    ///         vcpu::wait_until_next_schedule_slot();
+    ///         assert_eq!(schedule::current_plan().unwrap(), PlanId(2));
+    ///     })
+    ///     .plan(1)
+    ///     .slot(1, 5u32.ms())
+    ///     .plan(2)
+    ///     .slot(1, 10u32.ms())
+    ///     .run(2);
+    /// ```
+    pub fn plan(mut self, id: xcfPlanId_t) -> Self {
+        if let [(_, slots)] = self.plans.as_slice() {
+            if slots.is_empty() {
+                self.plans.clear();
+            }
+        }
+        self.plans.push((id, Vec::new()));
+        self
+    }
+
+    /// Connect the destination port `destination` to the source port `source`
+    pub fn connect(self, source: &str, destination: &str) -> Self {
+        self.world
+            .lock()
+            .routes
+            .insert(destination.to_owned(), source.to_owned());
+        self
+    }
+
+    /// Run the simulation for `major_frames` iterations of the major frame
+    ///
+    /// The returned [`Report`] tells the outcome of the simulation, e.g. the health monitor events
+    /// reported by the partitions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use xng_rs::{partition, sim::Simulator, time::DurationFromInt};
+    ///
+    /// let report = Simulator::new()
+    ///     .partition("Sensor", 1, || {
+    ///         xng_rs::println!("booted");
+    ///         partition::halt(partition::my_id().unwrap()).unwrap();
+    ///     })
+    ///     .slot(1, 5u32.ms())
+    ///     .run(2);
+    ///
+    /// assert!(report.is_halted(1));
+    /// assert_eq!(report.console_output(), "booted\n");
+    /// ```
+    pub fn run(self, major_frames: usize) -> Report {
+        let baton = &self.world.baton;
+
+        let threads: Vec<JoinHandle<Option<PanicPayload>>> = self
+            .partitions
+            .into_iter()
+            .map(|partition| {
+                let world = self.world.clone();
+                thread::Builder::new()
+                    .name(partition.name)
+                    .spawn(move || {
+                        enter(world.clone(), partition.id);
+                        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                            world.baton.wait_for_turn(partition.id);
+                            (partition.entry)();
+                        }));
+                        world.lock().finish(partition.id);
+                        world.baton.release(partition.id);
+                        result.err().filter(|payload| !payload.is::<Stop>())
+                    })
+                    .expect("unable to spawn partition thread")
+            })
+            .collect();
+
+        {
+            let mut state = self.world.lock();
+            state.plans = self.plans.iter().map(|(id, _)| *id).collect();
+            state.plan = self.plans[0].0;
+            state.next_plan = state.plan;
+        }
+
+        let mut slot_start = Duration::ZERO;
+        for _ in 0..major_frames {
+            let plan = self.world.lock().start_major_frame();
+            let (_, slots) = self
+                .plans
+                .iter()
+                .find(|(id, _)| *id == plan)
+                .expect("only known plans can be switched to");
+            for (slot_id, (partition, duration)) in slots.iter().enumerate() {
+                let runnable = {
+                    let mut state = self.world.lock();
+                    let start = slot_start.as_micros() as xTime_t;
+                    // a partition overrunning its slot delays the next one, time never goes back
+                    state.now = state.now.max(start);
+                    state.slot = Some(xVCpuSchedStatus_t {
+                        slotId: slot_id as xcfSlotId_t,
+                        slotStart: start,
+                        slotDuration: duration.as_micros() as xTimeSpan_t,
+                    });
+                    state.is_runnable(*partition)
+                };
+                if runnable {
+                    baton.run(*partition);
+                }
+                slot_start += *duration;
+            }
+        }
+        {
+            let mut state = self.world.lock();
+            state.now = state.now.max(slot_start.as_micros() as xTime_t);
+        }
+
+        baton.stop();
+        let mut first_panic = None;
+        for thread in threads {
+            let panic = thread.join().expect("partition thread died unexpectedly");
+            first_panic = first_panic.or(panic);
+        }
+        if let Some(payload) = first_panic {
+            panic::resume_unwind(payload);
+        }
+
+        Report { world: self.world }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::*;
+    use crate::{sim, time::DurationFromInt, time::Instant};
+
+    #[test]
+    fn overrunning_a_slot_does_not_turn_back_the_time() {
+        let (tx, rx) = mpsc::channel();
+        let tx2 = tx.clone();
+
+        Simulator::new()
+            .partition("Overrunning", 1, move || {
+                sim::advance(8u32.ms());
+                tx.send(Instant::now().unwrap()).unwrap();
+            })
+            .partition("Next", 2, move || {
+                tx2.send(Instant::now().unwrap()).unwrap();
+            })
+            .slot(1, 5u32.ms())
+            .slot(2, 5u32.ms())
+            .run(1);
+
+        let end_of_overrun = rx.recv().unwrap();
+        let start_of_next = rx.recv().unwrap();
+        assert_eq!(end_of_overrun.since_boot(), 8u32.ms());
+        assert_eq!(start_of_next, end_of_overrun);
+    }
+}
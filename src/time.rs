@@ -7,8 +7,14 @@
 //! substitute for `xTimeSpan_t`, while `Instant` replaces  `xTime_t`.
 
 pub use core::time::Duration;
-use core::{convert::TryInto, mem::MaybeUninit};
+use core::{
+    convert::TryInto,
+    mem::MaybeUninit,
+    ops::{Add, AddAssign, Sub, SubAssign},
+};
 
+#[cfg(feature = "vtimer")]
+use crate::bindings::xTimeSpan_t;
 use crate::{
     bindings::{xTime_t, XGetSystemTime},
     XngError,
@@ -18,7 +24,8 @@ use crate::{
 ///
 /// # Examples
 ///
-/// ```no_run
+#[cfg_attr(feature = "sim", doc = "```")]
+#[cfg_attr(not(feature = "sim"), doc = "```no_run")]
 /// use xng_rs::prelude::*;
 ///
 /// let duration_since_boot = time::since_boot();
@@ -34,23 +41,228 @@ pub fn since_boot() -> Result<Duration, XngError> {
     Ok(duration_from_xtime_t(time)?)
 }
 
+/// A point in time of the system clock
+///
+/// Other than `xTime_t`, an `Instant` is always finite: XNG's infinite time value is reported as
+/// [`TimeError::InfiniteTime`] instead. Instants are only comparable within one boot of the
+/// system.
+///
+/// # Examples
+///
+#[cfg_attr(feature = "sim", doc = "```")]
+#[cfg_attr(not(feature = "sim"), doc = "```no_run")]
+/// # fn main() -> Result<(), xng_rs::XngError> {
+/// use xng_rs::time::{DurationFromInt, Instant};
+///
+/// let start = Instant::now()?;
+/// let deadline = start + 10u32.ms();
+/// // ...
+/// if Instant::now()? > deadline {
+///     // took too long
+/// }
+/// let took = start.elapsed()?;
+/// # Ok(())}
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Instant(Duration);
+
+impl Instant {
+    /// The instant the system booted
+    pub const BOOT: Instant = Instant(Duration::ZERO);
+
+    /// The latest instant which can be represented as `xTime_t`
+    pub const MAX: Instant = Instant(Duration::from_micros(xTime_t::MAX as u64));
+
+    /// Get the current instant
+    pub fn now() -> Result<Self, XngError> {
+        Ok(Self(since_boot()?))
+    }
+
+    /// Get the instant `duration` after the boot of the system
+    ///
+    /// Returns `None` if it can not be represented as `xTime_t`.
+    pub fn from_since_boot(duration: Duration) -> Option<Self> {
+        Self::BOOT.checked_add(duration)
+    }
+
+    /// Get the time passed between the boot of the system and this instant
+    pub fn since_boot(&self) -> Duration {
+        self.0
+    }
+
+    /// Get the time passed since this instant
+    ///
+    /// Is zero if this instant lies in the future.
+    pub fn elapsed(&self) -> Result<Duration, XngError> {
+        Ok(Self::now()?.duration_since(*self))
+    }
+
+    /// Get the time passed from `earlier` to this instant
+    ///
+    /// Is zero if `earlier` is actually later than this instant.
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        self.0.saturating_sub(earlier.0)
+    }
+
+    /// Get the time passed from `earlier` to this instant
+    ///
+    /// Returns `None` if `earlier` is actually later than this instant.
+    pub fn checked_duration_since(&self, earlier: Instant) -> Option<Duration> {
+        self.0.checked_sub(earlier.0)
+    }
+
+    /// Add `duration` to this instant
+    ///
+    /// Returns `None` if the result can not be represented as `xTime_t`.
+    pub fn checked_add(&self, duration: Duration) -> Option<Instant> {
+        Some(Self(self.0.checked_add(duration)?)).filter(|instant| *instant <= Self::MAX)
+    }
+
+    /// Subtract `duration` from this instant
+    ///
+    /// Returns `None` if the result lies before the boot of the system.
+    pub fn checked_sub(&self, duration: Duration) -> Option<Instant> {
+        self.0.checked_sub(duration).map(Self)
+    }
+
+    /// Convert a `xTime_t` to an `Instant`
+    pub(crate) fn from_xtime_t(time: xTime_t) -> Result<Self, TimeError> {
+        duration_from_xtime_t(time).map(Self)
+    }
+
+    /// Convert this instant to a `xTime_t`
+    #[cfg(any(feature = "sampling", feature = "vtimer"))]
+    pub(crate) fn as_xtime_t(&self) -> xTime_t {
+        // an instant never exceeds `Instant::MAX`
+        self.0.as_micros() as xTime_t
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    /// # Panics
+    ///
+    /// Panics if the result can not be represented as `xTime_t`. Use [`Instant::checked_add`]
+    /// for a version without panic.
+    fn add(self, duration: Duration) -> Instant {
+        self.checked_add(duration)
+            .expect("overflow when adding duration to instant")
+    }
+}
+
+impl AddAssign<Duration> for Instant {
+    fn add_assign(&mut self, duration: Duration) {
+        *self = *self + duration;
+    }
+}
+
+impl Sub<Duration> for Instant {
+    type Output = Instant;
+
+    /// # Panics
+    ///
+    /// Panics if the result lies before the boot of the system. Use [`Instant::checked_sub`] for
+    /// a version without panic.
+    fn sub(self, duration: Duration) -> Instant {
+        self.checked_sub(duration)
+            .expect("overflow when subtracting duration from instant")
+    }
+}
+
+impl SubAssign<Duration> for Instant {
+    fn sub_assign(&mut self, duration: Duration) {
+        *self = *self - duration;
+    }
+}
+
+impl Sub<Instant> for Instant {
+    type Output = Duration;
+
+    /// Same as [`Instant::duration_since`]
+    fn sub(self, earlier: Instant) -> Duration {
+        self.duration_since(earlier)
+    }
+}
+
+/// The execution clock of the partition
+///
+/// Other than the system clock read by [`Instant::now`], the execution clock only advances while
+/// the partition is running. Thus its value is not an [`Instant`], but the execution time the
+/// partition consumed since boot.
+///
+/// # Examples
+///
+#[cfg_attr(feature = "sim", doc = "```")]
+#[cfg_attr(not(feature = "sim"), doc = "```no_run")]
+/// # fn main() -> Result<(), xng_rs::XngError> {
+/// use xng_rs::time::{ExecutionClock, Instant};
+///
+/// let start = Instant::now()?;
+/// let (_, consumed) = ExecutionClock::measure(|| {
+///     // do some work
+/// })?;
+/// let latency = start.elapsed()?;
+/// // the difference was spent in other partitions
+/// let preempted = latency.saturating_sub(consumed);
+/// # Ok(())}
+/// ```
+#[cfg(feature = "vclock")]
+pub struct ExecutionClock;
+
+#[cfg(feature = "vclock")]
+impl ExecutionClock {
+    /// Get the execution time the partition consumed since boot
+    pub fn now() -> Result<Duration, XngError> {
+        let mut time = MaybeUninit::uninit();
+        let time = unsafe {
+            let return_code = crate::bindings::XGetExecClock(time.as_mut_ptr());
+            XngError::from(return_code)?;
+            time.assume_init()
+        };
+
+        Ok(duration_from_xtime_t(time)?)
+    }
+
+    /// Get the execution time consumed by running `f`, together with its result
+    ///
+    /// If reading the clock after running `f` fails, its result is lost.
+    pub fn measure<T>(f: impl FnOnce() -> T) -> Result<(T, Duration), XngError> {
+        let start = Self::now()?;
+        let result = f();
+        Ok((result, Self::now()?.saturating_sub(start)))
+    }
+}
+
 /// Convert a xtime_t to a `Duration`
 ///
 /// This API is not to be published
 // TODO ^ is that clever?
 pub(crate) fn duration_from_xtime_t(time: xTime_t) -> Result<Duration, TimeError> {
     if time.is_negative() {
-        Err(TimeError::InfiniteTime.into())
+        Err(TimeError::InfiniteTime)
     } else {
         Ok(Duration::from_micros(time.try_into().unwrap())) // this should never fail
     }
 }
 
+/// Convert a `Duration` to a xTimeSpan_t
+#[cfg(feature = "vtimer")]
+pub(crate) fn xtime_span_from_duration(duration: Duration) -> Result<xTimeSpan_t, TimeError> {
+    duration
+        .as_micros()
+        .try_into()
+        .map_err(|_| TimeError::Overflow)
+}
+
 /// Error during operations with time
 #[derive(Debug)]
 pub enum TimeError {
     /// An instant has the value infinity. This should not happen in the foreseable future!
     InfiniteTime,
+
+    /// A duration is too long to be represented by XNG
+    Overflow,
 }
 
 /// Extension trait that adds convenience methods to the `i64` type
@@ -150,3 +362,45 @@ impl Into<KiloHertz> for MegaHertz {
 #[derive(PartialEq, PartialOrd, Clone, Copy, Debug)]
 pub struct MilliSeconds(pub u32);
 */
+
+#[cfg(all(test, feature = "sim"))]
+mod tests {
+    use super::*;
+    use crate::sim;
+
+    #[test]
+    fn now_follows_the_system_time() {
+        let start = Instant::now().unwrap();
+        sim::advance(3u32.ms());
+
+        assert_eq!(start.elapsed().unwrap(), 3u32.ms());
+        assert_eq!(Instant::now().unwrap() - start, 3u32.ms());
+        assert_eq!(since_boot().unwrap(), start.since_boot() + 3u32.ms());
+    }
+
+    #[test]
+    fn instant_arithmetic_saturates() {
+        let start = Instant::now().unwrap();
+        sim::advance(1u32.ms());
+        let now = Instant::now().unwrap();
+
+        assert_eq!(start.duration_since(now), Duration::ZERO);
+        assert_eq!(start.checked_duration_since(now), None);
+        assert_eq!(now.checked_sub(1u32.ms()), Some(start));
+        assert_eq!(Instant::MAX.checked_add(1u32.us()), None);
+    }
+
+    #[cfg(all(feature = "vclock", feature = "schedule"))]
+    #[test]
+    fn execution_clock_only_advances_while_running() {
+        let start = ExecutionClock::now().unwrap();
+        let (_, consumed) = ExecutionClock::measure(|| sim::advance(2u32.ms())).unwrap();
+        assert_eq!(consumed, 2u32.ms());
+
+        // the time spent waiting for the next slot is not accounted
+        let before_wait = Instant::now().unwrap();
+        crate::vcpu::wait_until_next_schedule_slot();
+        assert!(before_wait.elapsed().unwrap() > Duration::ZERO);
+        assert_eq!(ExecutionClock::now().unwrap(), start + 2u32.ms());
+    }
+}
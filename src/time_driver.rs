@@ -0,0 +1,89 @@
+//! Implementation of the `embassy-time` driver for XNG partitions
+//!
+//! The current time is the system time, counted in microseconds since boot. Alarms are realized
+//! with the timer of the hardware clock: whenever it expires, its vIRQ wakes the due tasks and
+//! rearms the timer for the next one. Therefore the driver takes over the timer of the hardware
+//! clock as well as the handler of [`virq::HW_TIMER`], and vIRQs have to be enabled.
+
+use core::{
+    cell::RefCell,
+    sync::atomic::{AtomicBool, Ordering},
+    task::Waker,
+};
+
+use critical_section::{CriticalSection, Mutex};
+use embassy_time_driver::Driver;
+use embassy_time_queue_utils::Queue;
+
+use crate::{
+    time::{self, Duration, Instant},
+    timer::{self, HwClock},
+    virq,
+};
+
+struct XngTimeDriver {
+    queue: Mutex<RefCell<Queue>>,
+    initialized: AtomicBool,
+}
+
+embassy_time_driver::time_driver_impl!(static DRIVER: XngTimeDriver = XngTimeDriver {
+    queue: Mutex::new(RefCell::new(Queue::new())),
+    initialized: AtomicBool::new(false),
+});
+
+impl XngTimeDriver {
+    /// Register the vIRQ handler of the timer, unless already done
+    fn init(&self) {
+        if self.initialized.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        virq::set_handler(virq::HW_TIMER, on_timer)
+            .and_then(|_| virq::unmask(virq::HW_TIMER))
+            .expect("the vIRQ of the hardware clock timer is always available");
+    }
+
+    /// Wake the due tasks and arm the timer for the next alarm
+    fn set_alarm(&self, cs: CriticalSection) {
+        let mut queue = self.queue.borrow_ref_mut(cs);
+        loop {
+            let next = queue.next_expiration(self.now());
+            let expiration = match Instant::from_since_boot(Duration::from_micros(next)) {
+                Some(expiration) => expiration,
+                // also the case if there is no alarm at all
+                None => {
+                    let _ = timer::cancel(HwClock);
+                    return;
+                }
+            };
+            timer::set_oneshot(HwClock, expiration)
+                .expect("an instant is always a valid expiration");
+
+            // the alarm may have passed while arming the timer
+            if self.now() < next {
+                return;
+            }
+        }
+    }
+}
+
+impl Driver for XngTimeDriver {
+    fn now(&self) -> u64 {
+        let now = time::since_boot().expect("the system time is always available");
+        now.as_micros() as u64
+    }
+
+    fn schedule_wake(&self, at: u64, waker: &Waker) {
+        self.init();
+        critical_section::with(|cs| {
+            let rearm = self.queue.borrow_ref_mut(cs).schedule_wake(at, waker);
+            if rearm {
+                self.set_alarm(cs);
+            }
+        })
+    }
+}
+
+fn on_timer(irq: virq::VIrq) {
+    let _ = virq::ack(irq);
+    critical_section::with(|cs| DRIVER.set_alarm(cs));
+}
@@ -0,0 +1,280 @@
+//! Functions related to the virtual timers
+//!
+//! Every partition has one virtual timer per clock. A timer is armed to expire at a point in time
+//! of its clock, either once or periodically. On expiry XNG raises a virtual interrupt to the
+//! partition.
+//!
+//! The [`HwClock`] advances with the system time, its points in time are [`Instant`]s as returned
+//! by [`Instant::now`]. The [`ExecClock`] only advances while the partition is running, its points
+//! in time are the execution time consumed since boot, given as [`Duration`].
+//!
+//! # Examples
+//!
+#![cfg_attr(feature = "sim", doc = "```")]
+#![cfg_attr(not(feature = "sim"), doc = "```no_run")]
+//! # fn main() -> Result<(), xng_rs::XngError> {
+//! use xng_rs::time::{DurationFromInt, Instant};
+//! use xng_rs::timer::{self, HwClock};
+//!
+//! // activate the control loop every 20 ms, starting in 5 ms
+//! let first = Instant::now()? + 5u32.ms();
+//! timer::set_periodic(HwClock, first, 20u32.ms())?;
+//!
+//! if let Some(status) = timer::status(HwClock)? {
+//!     let next_activation = status.expiration;
+//! }
+//!
+//! timer::cancel(HwClock)?;
+//! # Ok(())}
+//! ```
+
+use core::{fmt::Debug, mem::MaybeUninit};
+
+use self::private::RawTime;
+use crate::{
+    bindings,
+    time::{duration_from_xtime_t, xtime_span_from_duration, Duration, Instant},
+    XngError,
+};
+
+/// A clock a timer is based on, either [`HwClock`] or [`ExecClock`]
+pub trait Clock: Copy + private::Sealed {
+    /// The type of the points in time of the clock
+    type Time: Copy + Debug + Eq + private::RawTime;
+}
+
+/// The system clock, which advances continuously
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HwClock;
+
+impl Clock for HwClock {
+    type Time = Instant;
+}
+
+/// The execution clock of the partition, which only advances while the partition is running
+///
+/// Its points in time are the execution time the partition consumed since boot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExecClock;
+
+impl Clock for ExecClock {
+    type Time = Duration;
+}
+
+mod private {
+    use crate::{bindings, time::TimeError};
+
+    /// Restricts the implementations of `Clock` to this crate
+    pub trait Sealed {
+        const ID: bindings::xClockId_t;
+    }
+
+    impl Sealed for super::HwClock {
+        const ID: bindings::xClockId_t = bindings::xHwClock;
+    }
+
+    impl Sealed for super::ExecClock {
+        const ID: bindings::xClockId_t = bindings::xExecClock;
+    }
+
+    /// A point in time of a clock, convertible from and to `xTime_t`
+    pub trait RawTime: Sized {
+        fn from_raw(time: bindings::xTime_t) -> Result<Self, TimeError>;
+        fn to_raw(self) -> Result<bindings::xTime_t, TimeError>;
+    }
+
+    impl RawTime for crate::time::Instant {
+        fn from_raw(time: bindings::xTime_t) -> Result<Self, TimeError> {
+            Self::from_xtime_t(time)
+        }
+
+        fn to_raw(self) -> Result<bindings::xTime_t, TimeError> {
+            Ok(self.as_xtime_t())
+        }
+    }
+
+    impl RawTime for crate::time::Duration {
+        fn from_raw(time: bindings::xTime_t) -> Result<Self, TimeError> {
+            crate::time::duration_from_xtime_t(time)
+        }
+
+        fn to_raw(self) -> Result<bindings::xTime_t, TimeError> {
+            self.as_micros().try_into().map_err(|_| TimeError::Overflow)
+        }
+    }
+}
+
+/// The current status of an armed timer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimerStatus<T> {
+    /// The point in time the timer expires next
+    pub expiration: T,
+
+    /// The period of the timer - None if it expires only once
+    pub period: Option<Duration>,
+}
+
+/// Arm the timer of `clock` to expire once at `expiration`
+///
+/// A previously armed timer of the same clock is replaced.
+///
+/// # Examples
+///
+#[cfg_attr(feature = "sim", doc = "```")]
+#[cfg_attr(not(feature = "sim"), doc = "```no_run")]
+/// # fn main() -> Result<(), xng_rs::XngError> {
+/// use xng_rs::time::{DurationFromInt, ExecutionClock, Instant};
+/// use xng_rs::timer::{self, ExecClock, HwClock};
+///
+/// timer::set_oneshot(HwClock, Instant::now()? + 100u32.ms())?;
+///
+/// // expires once the partition ran for another 2 ms
+/// timer::set_oneshot(ExecClock, ExecutionClock::now()? + 2u32.ms())?;
+/// # Ok(())}
+/// ```
+pub fn set_oneshot<C: Clock>(clock: C, expiration: C::Time) -> Result<(), XngError> {
+    set(clock, expiration, 0)
+}
+
+/// Arm the timer of `clock` to expire at `first` and then every `period`
+///
+/// A previously armed timer of the same clock is replaced. `period` must not be zero.
+pub fn set_periodic<C: Clock>(clock: C, first: C::Time, period: Duration) -> Result<(), XngError> {
+    if period.is_zero() {
+        return Err(XngError::InvalidParam);
+    }
+    set(clock, first, xtime_span_from_duration(period)?)
+}
+
+fn set<C: Clock>(
+    _clock: C,
+    expiration: C::Time,
+    interval: bindings::xTimeSpan_t,
+) -> Result<(), XngError> {
+    let expiration = expiration.to_raw()?;
+    let return_code = unsafe { bindings::XSetTimer(C::ID, expiration, interval) };
+    XngError::from(return_code)
+}
+
+/// Disarm the timer of `clock`
+pub fn cancel<C: Clock>(_clock: C) -> Result<(), XngError> {
+    let return_code = unsafe { bindings::XCancelTimer(C::ID) };
+    XngError::from(return_code)
+}
+
+/// Get the status of the timer of `clock` - None if it is not armed
+pub fn status<C: Clock>(_clock: C) -> Result<Option<TimerStatus<C::Time>>, XngError> {
+    let mut expiration = MaybeUninit::uninit();
+    let mut interval = MaybeUninit::uninit();
+
+    let (expiration, interval) = unsafe {
+        let return_code =
+            bindings::XGetTimer(C::ID, expiration.as_mut_ptr(), interval.as_mut_ptr());
+        XngError::from(return_code)?;
+        (expiration.assume_init(), interval.assume_init())
+    };
+
+    // a disarmed timer expires at infinity
+    let expiration = match C::Time::from_raw(expiration) {
+        Ok(expiration) => expiration,
+        Err(_) => return Ok(None),
+    };
+    let period = match interval {
+        0 => None,
+        interval => Some(duration_from_xtime_t(interval)?),
+    };
+
+    Ok(Some(TimerStatus { expiration, period }))
+}
+
+#[cfg(all(test, feature = "sim"))]
+mod tests {
+    use super::*;
+    use crate::{sim, time::DurationFromInt};
+
+    #[test]
+    fn oneshot_timer_expires_once() {
+        let expiration = Instant::now().unwrap() + 5u32.ms();
+        set_oneshot(HwClock, expiration).unwrap();
+        assert_eq!(
+            status(HwClock).unwrap(),
+            Some(TimerStatus {
+                expiration,
+                period: None
+            })
+        );
+
+        sim::advance(4u32.ms());
+        assert!(status(HwClock).unwrap().is_some());
+        sim::advance(1u32.ms());
+        assert_eq!(status(HwClock).unwrap(), None);
+    }
+
+    #[test]
+    fn periodic_timer_is_rearmed() {
+        let first = Instant::now().unwrap() + 5u32.ms();
+        set_periodic(HwClock, first, 10u32.ms()).unwrap();
+
+        sim::advance(5u32.ms());
+        let status_after_first = status(HwClock).unwrap().unwrap();
+        assert_eq!(status_after_first.expiration, first + 10u32.ms());
+        assert_eq!(status_after_first.period, Some(10u32.ms()));
+
+        // missed expirations are skipped
+        sim::advance(25u32.ms());
+        assert_eq!(
+            status(HwClock).unwrap().unwrap().expiration,
+            first + 30u32.ms()
+        );
+
+        cancel(HwClock).unwrap();
+        assert_eq!(status(HwClock).unwrap(), None);
+    }
+
+    #[test]
+    fn zero_period_is_rejected() {
+        let first = Instant::now().unwrap();
+        assert!(matches!(
+            set_periodic(HwClock, first, Duration::ZERO),
+            Err(XngError::InvalidParam)
+        ));
+        assert_eq!(status(HwClock).unwrap(), None);
+    }
+
+    #[cfg(all(feature = "vclock", feature = "schedule"))]
+    #[test]
+    fn exec_timer_follows_the_execution_time() {
+        let expiration = crate::time::ExecutionClock::now().unwrap() + 2u32.ms();
+        set_oneshot(ExecClock, expiration).unwrap();
+
+        // the execution clock does not advance while waiting for the next slot
+        crate::vcpu::wait_until_next_schedule_slot();
+        assert_eq!(status(ExecClock).unwrap().unwrap().expiration, expiration);
+
+        sim::advance(2u32.ms());
+        assert_eq!(status(ExecClock).unwrap(), None);
+    }
+
+    #[cfg(feature = "virq")]
+    #[test]
+    fn expiry_raises_the_timer_virq() {
+        use crate::virq;
+        use core::sync::atomic::{AtomicU32, Ordering};
+
+        static EXPIRIES: AtomicU32 = AtomicU32::new(0);
+
+        virq::set_handler(virq::HW_TIMER, |_| {
+            EXPIRIES.fetch_add(1, Ordering::Relaxed);
+        })
+        .unwrap();
+        virq::unmask(virq::HW_TIMER).unwrap();
+        virq::enable().unwrap();
+
+        let first = Instant::now().unwrap() + 1u32.ms();
+        set_periodic(HwClock, first, 2u32.ms()).unwrap();
+        sim::advance(1u32.ms());
+        assert_eq!(EXPIRIES.load(Ordering::Relaxed), 1);
+        sim::advance(2u32.ms());
+        assert_eq!(EXPIRIES.load(Ordering::Relaxed), 2);
+    }
+}
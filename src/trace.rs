@@ -0,0 +1,146 @@
+//! Functions related to the trace facility
+//!
+//! XNG records trace events of the partitions, which can be retrieved e.g. via the SKE or a debug
+//! link. Each event consists of an application defined code and a small payload.
+//!
+//! With the `log` feature enabled, this module also provides a backend for the `log` crate, so
+//! that partition code and third-party crates can log through the trace facility.
+//!
+//! # Examples
+//!
+#![cfg_attr(feature = "sim", doc = "```")]
+#![cfg_attr(not(feature = "sim"), doc = "```no_run")]
+//! # fn main() -> Result<(), xng_rs::XngError> {
+//! use xng_rs::trace;
+//!
+//! const ALTITUDE_UPDATE: trace::TraceCode = 0x100;
+//!
+//! let altitude: u32 = 1337;
+//! trace::event(ALTITUDE_UPDATE, &altitude.to_le_bytes())?;
+//! # Ok(())}
+//! ```
+
+use core::fmt;
+
+use crate::{bindings, buf::FmtBuffer, XngError};
+
+/// The maximum length of the payload of a trace event
+pub const MAX_PAYLOAD_LENGTH: usize = bindings::xMaxTracePayloadLength as usize;
+
+/// Identifies the kind of a trace event
+pub type TraceCode = bindings::xTraceCode_t;
+
+/// Record a trace event
+///
+/// `payload` must be smaller or equal in size to [`MAX_PAYLOAD_LENGTH`].
+pub fn event(code: TraceCode, payload: &[u8]) -> Result<(), XngError> {
+    if payload.len() > MAX_PAYLOAD_LENGTH {
+        return Err(XngError::BufTooBig {
+            buf_size: payload.len(),
+            max_allowed: MAX_PAYLOAD_LENGTH,
+        });
+    }
+
+    let return_code = unsafe {
+        bindings::XTraceEvent(
+            code,
+            payload.as_ptr() as *mut _, // TODO fix to non mut pointer
+            payload.len() as u32,       // TODO fix to usize
+        )
+    };
+    XngError::from(return_code)
+}
+
+/// Record a trace event with a formatted text as payload
+///
+/// A text longer than [`MAX_PAYLOAD_LENGTH`] is truncated, its end is replaced by `...`.
+pub fn event_fmt(code: TraceCode, args: fmt::Arguments) -> Result<(), XngError> {
+    let mut payload = FmtBuffer::<MAX_PAYLOAD_LENGTH>::new();
+    // the buffer truncates instead of failing
+    let _ = fmt::write(&mut payload, args);
+    payload.mark_truncation();
+    event(code, payload.as_bytes())
+}
+
+#[cfg(feature = "log")]
+pub use logger::*;
+
+#[cfg(feature = "log")]
+mod logger {
+    use super::{event_fmt, TraceCode};
+
+    /// A backend for the `log` crate, which records every log message as trace event
+    ///
+    /// The trace codes of the events start at the base code given to [`TraceLogger::new`]: the
+    /// base code itself for `Level::Error`, up to the base code plus `4` for `Level::Trace`. Choose
+    /// the base code so that these five codes do not collide with the ones the application passes
+    /// to [`event`](super::event). The payload is the formatted message, prefixed by the level and
+    /// target.
+    ///
+    /// # Examples
+    ///
+    #[cfg_attr(feature = "sim", doc = "```")]
+    #[cfg_attr(not(feature = "sim"), doc = "```no_run")]
+    /// use xng_rs::trace::{self, TraceLogger};
+    ///
+    /// static LOGGER: TraceLogger = TraceLogger::new(0xFF00);
+    ///
+    /// trace::init_logger(&LOGGER, log::LevelFilter::Info).unwrap();
+    /// log::info!("partition started");
+    /// ```
+    pub struct TraceLogger {
+        base_code: TraceCode,
+    }
+
+    impl TraceLogger {
+        /// Create a logger recording its events with the trace codes from `base_code` on
+        pub const fn new(base_code: TraceCode) -> Self {
+            Self { base_code }
+        }
+
+        /// The trace code of the events of `level`
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use xng_rs::trace::TraceLogger;
+        ///
+        /// let logger = TraceLogger::new(0xFF00);
+        /// assert_eq!(logger.code(log::Level::Error), 0xFF00);
+        /// assert_eq!(logger.code(log::Level::Trace), 0xFF04);
+        /// ```
+        pub fn code(&self, level: log::Level) -> TraceCode {
+            // the levels are numbered from 1 for `Level::Error`
+            self.base_code.wrapping_add(level as TraceCode - 1)
+        }
+    }
+
+    /// Install `logger` as logger, recording messages up to `level`
+    pub fn init_logger(
+        logger: &'static TraceLogger,
+        level: log::LevelFilter,
+    ) -> Result<(), log::SetLoggerError> {
+        log::set_logger(logger)?;
+        log::set_max_level(level);
+        Ok(())
+    }
+
+    impl log::Log for TraceLogger {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata.level() <= log::max_level()
+        }
+
+        fn log(&self, record: &log::Record) {
+            if !self.enabled(record.metadata()) {
+                return;
+            }
+            // a failing logger must not disturb the partition
+            let _ = event_fmt(
+                self.code(record.level()),
+                format_args!("{} {}: {}", record.level(), record.target(), record.args()),
+            );
+        }
+
+        fn flush(&self) {}
+    }
+}
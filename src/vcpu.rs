@@ -1,17 +1,129 @@
 //! This module contains functions for the virtual CPU
 
-use crate::bindings;
+use core::mem::MaybeUninit;
+
+use crate::{
+    bindings,
+    time::{duration_from_xtime_t, Duration, Instant},
+    XngError,
+};
 
 /// Type representing the id of a virtual CPU
-pub struct VCpuId(bindings::xVCpuId_t);
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VCpuId(pub bindings::xVCpuId_t);
+
+impl VCpuId {
+    /// The first vCpu of the partition, the only one of a partition running on a single vCpu
+    pub const FIRST: Self = Self(0);
+}
+
+/// Type representing the id of a schedule slot, as defined via XCF
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SlotId(pub bindings::xcfSlotId_t);
 
 /// Yields the computation time of the current vCpu to the hypervisor until the start of a new
 /// slot.
+#[cfg(feature = "schedule")]
 pub fn wait_until_next_schedule_slot() {
     unsafe { bindings::XWaitUntilNextScheduleSlot() };
 }
 
+/// Get the time left in the current slot of the calling vCpu
+///
+/// The partition must run on a single vCpu, see [`VCpuId::FIRST`].
+///
+/// # Examples
+///
+#[cfg_attr(feature = "sim", doc = "```")]
+#[cfg_attr(not(feature = "sim"), doc = "```no_run")]
+/// # fn main() -> Result<(), xng_rs::XngError> {
+/// use xng_rs::{time::DurationFromInt, vcpu};
+///
+/// if vcpu::remaining_slot_time()? > 2u32.ms() {
+///     // there is time for another iteration
+/// }
+/// # Ok(())}
+/// ```
+pub fn remaining_slot_time() -> Result<Duration, XngError> {
+    let slot_end = current_slot()?.slot_end();
+    Ok(slot_end.duration_since(Instant::now()?))
+}
+
+/// Run work items until `margin` before the end of the current slot, then yield the slot
+///
+/// `work` runs one work item per call and returns whether there is more work to do. It is called
+/// again as long as more than `margin` of the slot is left. Thus `margin` must cover the worst case
+/// execution time of a single work item, so that no work item spills across the slot boundary.
+/// Once there is no more work or time, the rest of the slot is yielded.
+///
+/// The partition must run on a single vCpu, see [`VCpuId::FIRST`].
+///
+/// # Examples
+///
+/// ```no_run
+/// # fn main() -> Result<(), xng_rs::XngError> {
+/// use xng_rs::{time::DurationFromInt, vcpu};
+///
+/// let mut dirty_blocks = [true; 64].into_iter();
+/// loop {
+///     vcpu::work_until_slot_end(500u32.us(), || match dirty_blocks.next() {
+///         Some(_block) => true, // scrub the block
+///         None => false,
+///     })?;
+/// }
+/// # }
+/// ```
+#[cfg(feature = "schedule")]
+pub fn work_until_slot_end<F>(margin: Duration, mut work: F) -> Result<(), XngError>
+where
+    F: FnMut() -> bool,
+{
+    if let Some(deadline) = current_slot()?.slot_end().checked_sub(margin) {
+        while Instant::now()? < deadline && work() {}
+    }
+    wait_until_next_schedule_slot();
+    Ok(())
+}
+
+/// Get the status of the vCpu `vcpu`
+///
+/// # Examples
+///
+#[cfg_attr(feature = "sim", doc = "```")]
+#[cfg_attr(not(feature = "sim"), doc = "```no_run")]
+/// # fn main() -> Result<(), xng_rs::XngError> {
+/// use xng_rs::vcpu::{self, VCpuId, VCpuState};
+///
+/// let status = vcpu::status(VCpuId::FIRST)?;
+/// assert_eq!(status.state, VCpuState::Running);
+/// if let Some(slot) = status.slot {
+///     let slot_end = slot.slot_end();
+/// }
+/// # Ok(())}
+/// ```
+pub fn status(vcpu: VCpuId) -> Result<VCpuStatus, XngError> {
+    let raw = raw_status(vcpu)?;
+    let state = VCpuState::try_from(raw.state)?;
+    let slot = match state {
+        VCpuState::Running => Some(VCpuSchedStatus::from_raw(raw.schedStatus)?),
+        _ => None,
+    };
+
+    Ok(VCpuStatus { state, slot })
+}
+
+/// The status of a vCpu
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VCpuStatus {
+    /// The current state of the vCpu
+    pub state: VCpuState,
+
+    /// The status of the current slot - None unless the vCpu is running
+    pub slot: Option<VCpuSchedStatus>,
+}
+
 /// Definition of the  vCpu's current state type
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[repr(u32)]
 pub enum VCpuState {
     /// The vCpu is eligible to run but it has not been selected by the scheduler
@@ -35,12 +147,69 @@ pub enum VCpuState {
     Waiting = bindings::xVCpuWaiting,
 }
 
+impl TryFrom<bindings::xVCpuState_t> for VCpuState {
+    type Error = XngError;
+
+    /// Returns `Err(XngError::UnknownVCpuState)` for values not defined by XNG
+    fn try_from(raw: bindings::xVCpuState_t) -> Result<Self, Self::Error> {
+        match raw {
+            bindings::xVCpuReady => Ok(Self::Ready),
+            bindings::xVCpuRunning => Ok(Self::Running),
+            bindings::xVCpuIdle => Ok(Self::Idle),
+            bindings::xVCpuSuspended => Ok(Self::Suspended),
+            bindings::xVCpuWaiting => Ok(Self::Waiting),
+            raw => Err(XngError::UnknownVCpuState(raw)),
+        }
+    }
+}
+
 /// Status of the current schedule slot when vCpu is in xVCpuRunning state
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct VCpuSchedStatus {
     /// Current slot's identifier
-    slot_id: bindings::xcfSlotId_t,
-    slot_start: core::time::Duration,
-    slot_duration: core::time::Duration,
+    pub slot_id: SlotId,
+
+    /// The instant the current slot started
+    pub slot_start: Instant,
+
+    /// The duration of the current slot
+    pub slot_duration: Duration,
+}
+
+impl VCpuSchedStatus {
+    fn from_raw(raw: bindings::xVCpuSchedStatus_t) -> Result<Self, XngError> {
+        Ok(Self {
+            slot_id: SlotId(raw.slotId),
+            slot_start: Instant::from_xtime_t(raw.slotStart)?,
+            slot_duration: duration_from_xtime_t(raw.slotDuration)?,
+        })
+    }
+
+    /// The instant the current slot ends
+    pub fn slot_end(&self) -> Instant {
+        self.slot_start
+            .checked_add(self.slot_duration)
+            .unwrap_or(Instant::MAX)
+    }
+}
+
+/// Get the status of the slot the calling vCpu is running in
+///
+/// As the id of the calling vCpu is not yet provided in SKE, the partition is assumed to run on a
+/// single vCpu.
+pub(crate) fn current_slot() -> Result<VCpuSchedStatus, XngError> {
+    // the calling vCpu is running by definition
+    status(VCpuId::FIRST)?.slot.ok_or(XngError::InvalidMode)
+}
+
+fn raw_status(vcpu: VCpuId) -> Result<bindings::xVCpuStatus_t, XngError> {
+    let mut status = MaybeUninit::uninit();
+
+    unsafe {
+        let return_code = bindings::XGetVCpuStatus(vcpu.0, status.as_mut_ptr());
+        XngError::from(return_code)?;
+        Ok(status.assume_init())
+    }
 }
 
 /* These symbols are not yet provided in SKE
@@ -73,3 +242,84 @@ pub fn resume(cpu: VCpuId)->Result<(), XngError>{
  * What is xMemAddr_t for?
  * xReturnCode_t XResetVCpu(xVCpuId_t, xMemAddr_t);
  */
+
+#[cfg(all(test, feature = "sim"))]
+mod tests {
+    use super::*;
+    use crate::{sim, time::DurationFromInt};
+
+    #[test]
+    fn first_vcpu_is_running() {
+        sim::set_slot_period(10u32.ms());
+        sim::advance(12u32.ms());
+
+        let status = status(VCpuId::FIRST).unwrap();
+        assert_eq!(status.state, VCpuState::Running);
+        let slot = status.slot.unwrap();
+        assert_eq!(slot.slot_id, SlotId(0));
+        assert_eq!(slot.slot_start.since_boot(), 10u32.ms());
+        assert_eq!(slot.slot_end().since_boot(), 20u32.ms());
+    }
+
+    #[test]
+    fn unknown_vcpus_are_rejected() {
+        assert!(matches!(status(VCpuId(1)), Err(XngError::InvalidParam)));
+    }
+
+    #[test]
+    fn unknown_states_are_rejected() {
+        assert_eq!(
+            VCpuState::try_from(bindings::xVCpuWaiting).unwrap(),
+            VCpuState::Waiting
+        );
+        assert!(matches!(
+            VCpuState::try_from(0xdead),
+            Err(XngError::UnknownVCpuState(0xdead))
+        ));
+    }
+
+    #[test]
+    fn remaining_slot_time_counts_down() {
+        sim::set_slot_period(10u32.ms());
+        sim::advance(3u32.ms());
+        assert_eq!(remaining_slot_time().unwrap(), 7u32.ms());
+    }
+
+    #[test]
+    fn work_stops_at_the_margin() {
+        sim::set_slot_period(10u32.ms());
+        let mut items = 0;
+
+        work_until_slot_end(3u32.ms(), || {
+            items += 1;
+            sim::advance(2u32.ms());
+            true
+        })
+        .unwrap();
+        // items start at 0, 2, 4 and 6ms, the one at 8ms would spill into the margin
+        assert_eq!(items, 4);
+        assert_eq!(Instant::now().unwrap().since_boot(), 10u32.ms());
+    }
+
+    #[test]
+    fn work_stops_when_done() {
+        sim::set_slot_period(10u32.ms());
+        let mut items = 0;
+
+        work_until_slot_end(3u32.ms(), || {
+            items += 1;
+            items < 2
+        })
+        .unwrap();
+        assert_eq!(items, 2);
+        assert_eq!(Instant::now().unwrap().since_boot(), 10u32.ms());
+    }
+
+    #[test]
+    fn no_work_if_the_margin_exceeds_the_slot() {
+        sim::set_slot_period(10u32.ms());
+
+        work_until_slot_end(20u32.ms(), || panic!("no time for work")).unwrap();
+        assert_eq!(Instant::now().unwrap().since_boot(), 10u32.ms());
+    }
+}
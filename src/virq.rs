@@ -0,0 +1,335 @@
+//! Functions related to the virtual interrupts
+//!
+//! XNG notifies a partition about events like an expired timer via virtual interrupts (vIRQ).
+//! Every vIRQ line can be masked separately, while the delivery of all vIRQs can be enabled or
+//! disabled at once. A pending vIRQ is delivered once it is unmasked and vIRQs are enabled.
+//!
+//! Rust functions are registered as handlers via [`set_handler`]. They are called in interrupt
+//! context, i.e. they preempt the normal execution of the partition. Data shared with a handler
+//! must therefore be accessed either atomically or with vIRQs disabled, see [`free`].
+//!
+//! # Examples
+//!
+#![cfg_attr(feature = "sim", doc = "```")]
+#![cfg_attr(not(feature = "sim"), doc = "```no_run")]
+//! # fn main() -> Result<(), xng_rs::XngError> {
+//! use core::sync::atomic::{AtomicU32, Ordering};
+//! use xng_rs::virq::{self, VIrq};
+//!
+//! static TICKS: AtomicU32 = AtomicU32::new(0);
+//!
+//! fn on_timer(irq: VIrq) {
+//!     TICKS.fetch_add(1, Ordering::Relaxed);
+//!     let _ = virq::ack(irq);
+//! }
+//!
+//! virq::set_handler(virq::HW_TIMER, on_timer)?;
+//! virq::unmask(virq::HW_TIMER)?;
+//! virq::enable()?;
+//! # Ok(())}
+//! ```
+
+#[cfg(not(feature = "sim"))]
+use core::{
+    mem,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+use crate::{bindings, XngError};
+
+/// The number of a virtual interrupt line
+pub type VIrq = bindings::xVIrq_t;
+
+/// A function handling a virtual interrupt
+pub type Handler = fn(VIrq);
+
+/// The number of virtual interrupt lines
+pub const NUM_VIRQS: usize = bindings::xNoVIrqs as usize;
+
+/// The vIRQ raised when the timer of the hardware clock expires
+pub const HW_TIMER: VIrq = bindings::xVIrqHwTimer;
+
+/// The vIRQ raised when the timer of the execution clock expires
+pub const EXEC_TIMER: VIrq = bindings::xVIrqExecTimer;
+
+/// The registered handlers, a null pointer denotes no handler
+///
+/// Only ever contains null or pointers converted from a [`Handler`]. The simulation keeps the
+/// handlers per partition instead, as its partitions share this static.
+#[cfg(not(feature = "sim"))]
+static HANDLERS: [AtomicPtr<()>; NUM_VIRQS] = {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const NONE: AtomicPtr<()> = AtomicPtr::new(core::ptr::null_mut());
+    [NONE; NUM_VIRQS]
+};
+
+/// Enable the delivery of vIRQs
+///
+/// Returns `XngError::NoAction` if vIRQs were already enabled.
+pub fn enable() -> Result<(), XngError> {
+    let return_code = unsafe { bindings::XEnableIrqs() };
+    XngError::from(return_code)
+}
+
+/// Disable the delivery of vIRQs
+///
+/// vIRQs raised in the meantime stay pending. Returns `XngError::NoAction` if vIRQs were already
+/// disabled.
+pub fn disable() -> Result<(), XngError> {
+    let return_code = unsafe { bindings::XDisableIrqs() };
+    XngError::from(return_code)
+}
+
+/// Run `f` with the delivery of vIRQs disabled
+///
+/// Afterwards the delivery is restored to its previous state, so calls may be nested. With the
+/// `critical-section` feature enabled, `f` runs in a critical section, which also excludes the
+/// other vCPUs of the partition.
+///
+/// # Panics
+///
+/// Panics if the delivery of vIRQs can not be disabled.
+///
+/// # Examples
+///
+#[cfg_attr(feature = "sim", doc = "```")]
+#[cfg_attr(not(feature = "sim"), doc = "```no_run")]
+/// use xng_rs::virq;
+///
+/// virq::free(|| {
+///     // no handler preempts this
+/// });
+/// ```
+pub fn free<R>(f: impl FnOnce() -> R) -> R {
+    #[cfg(feature = "critical-section")]
+    {
+        critical_section::with(|_| f())
+    }
+
+    #[cfg(not(feature = "critical-section"))]
+    {
+        let were_enabled = disable_nested();
+        let result = f();
+        if were_enabled {
+            let _ = enable();
+        }
+        result
+    }
+}
+
+/// Disable the delivery of vIRQs, returning whether it was enabled before
+///
+/// Panics if the delivery can not be disabled, as the caller relies on it being disabled.
+pub(crate) fn disable_nested() -> bool {
+    match disable() {
+        Ok(()) => true,
+        Err(XngError::NoAction) => false,
+        Err(e) => panic!("unable to disable the delivery of vIRQs: {:?}", e),
+    }
+}
+
+/// Mask the vIRQ `irq`, so that it is not delivered
+pub fn mask(irq: VIrq) -> Result<(), XngError> {
+    let return_code = unsafe { bindings::XMaskIrq(irq) };
+    XngError::from(return_code)
+}
+
+/// Unmask the vIRQ `irq`, so that it is delivered
+pub fn unmask(irq: VIrq) -> Result<(), XngError> {
+    let return_code = unsafe { bindings::XUnmaskIrq(irq) };
+    XngError::from(return_code)
+}
+
+/// Acknowledge the vIRQ `irq`, clearing it if pending
+pub fn ack(irq: VIrq) -> Result<(), XngError> {
+    let return_code = unsafe { bindings::XAckIrq(irq) };
+    XngError::from(return_code)
+}
+
+/// Register `handler` to be called whenever the vIRQ `irq` is delivered
+///
+/// A previously registered handler of `irq` is replaced. The handler is called with vIRQs
+/// disabled and has to [`ack`] the vIRQ itself, if necessary.
+pub fn set_handler(irq: VIrq, handler: Handler) -> Result<(), XngError> {
+    // the handler is in place before the trampoline can be called for it
+    store_handler(irq, Some(handler))?;
+
+    let return_code = unsafe { bindings::XSetIrqHandler(irq, Some(trampoline)) };
+    XngError::from(return_code)
+}
+
+/// Remove the handler of the vIRQ `irq`
+///
+/// A delivered vIRQ without handler is ignored.
+pub fn remove_handler(irq: VIrq) -> Result<(), XngError> {
+    store_handler(irq, None)
+}
+
+/// The handler XNG calls for every vIRQ, which dispatches to the registered [`Handler`]
+///
+/// A panicking handler does not unwind into XNG, as the panic can not cross the `extern "C"`
+/// boundary. With the `critical-section` feature enabled, the handler runs in a critical section.
+unsafe extern "C" fn trampoline(irq: bindings::xVIrq_t) {
+    // vIRQs are already disabled, the lock additionally excludes the other vCPUs
+    #[cfg(feature = "critical-section")]
+    crate::cs::lock();
+
+    if let Some(handler) = load_handler(irq) {
+        handler(irq)
+    }
+
+    #[cfg(feature = "critical-section")]
+    crate::cs::unlock();
+}
+
+#[cfg(not(feature = "sim"))]
+fn store_handler(irq: VIrq, handler: Option<Handler>) -> Result<(), XngError> {
+    let slot = HANDLERS.get(irq as usize).ok_or(XngError::InvalidParam)?;
+    let handler = match handler {
+        Some(handler) => handler as *mut (),
+        None => core::ptr::null_mut(),
+    };
+    slot.store(handler, Ordering::Release);
+    Ok(())
+}
+
+#[cfg(not(feature = "sim"))]
+fn load_handler(irq: VIrq) -> Option<Handler> {
+    let handler = HANDLERS.get(irq as usize)?.load(Ordering::Acquire);
+    if handler.is_null() {
+        return None;
+    }
+    // SAFETY: non-null pointers in `HANDLERS` were converted from a `Handler`
+    Some(unsafe { mem::transmute::<*mut (), Handler>(handler) })
+}
+
+#[cfg(feature = "sim")]
+fn store_handler(irq: VIrq, handler: Option<Handler>) -> Result<(), XngError> {
+    if crate::sim::set_rust_handler(irq, handler) {
+        Ok(())
+    } else {
+        Err(XngError::InvalidParam)
+    }
+}
+
+#[cfg(feature = "sim")]
+fn load_handler(irq: VIrq) -> Option<Handler> {
+    crate::sim::rust_handler(irq)
+}
+
+#[cfg(all(test, feature = "sim"))]
+mod tests {
+    use core::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+    use crate::{partition, sim};
+
+    #[test]
+    fn pending_virq_is_delivered_once_unmasked_and_enabled() {
+        static CALLS: AtomicU32 = AtomicU32::new(0);
+
+        set_handler(10, |_| {
+            CALLS.fetch_add(1, Ordering::Relaxed);
+        })
+        .unwrap();
+        sim::raise_virq(partition::my_id().unwrap(), 10);
+        assert_eq!(CALLS.load(Ordering::Relaxed), 0);
+
+        unmask(10).unwrap();
+        assert_eq!(CALLS.load(Ordering::Relaxed), 0);
+
+        enable().unwrap();
+        assert_eq!(CALLS.load(Ordering::Relaxed), 1);
+        assert!(matches!(enable(), Err(XngError::NoAction)));
+    }
+
+    #[test]
+    fn masked_virq_is_not_delivered() {
+        static CALLS: AtomicU32 = AtomicU32::new(0);
+
+        set_handler(10, |_| {
+            CALLS.fetch_add(1, Ordering::Relaxed);
+        })
+        .unwrap();
+        enable().unwrap();
+        unmask(10).unwrap();
+        mask(10).unwrap();
+
+        sim::raise_virq(partition::my_id().unwrap(), 10);
+        assert_eq!(CALLS.load(Ordering::Relaxed), 0);
+        unmask(10).unwrap();
+        assert_eq!(CALLS.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn free_defers_the_delivery() {
+        static CALLS: AtomicU32 = AtomicU32::new(0);
+
+        set_handler(10, |_| {
+            CALLS.fetch_add(1, Ordering::Relaxed);
+        })
+        .unwrap();
+        unmask(10).unwrap();
+        enable().unwrap();
+
+        free(|| {
+            sim::raise_virq(partition::my_id().unwrap(), 10);
+            assert_eq!(CALLS.load(Ordering::Relaxed), 0);
+
+            // nested sections keep the delivery disabled
+            free(|| sim::raise_virq(partition::my_id().unwrap(), 10));
+            assert_eq!(CALLS.load(Ordering::Relaxed), 0);
+        });
+        assert_eq!(CALLS.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn removed_handler_is_not_called() {
+        static CALLS: AtomicU32 = AtomicU32::new(0);
+
+        set_handler(10, |_| {
+            CALLS.fetch_add(1, Ordering::Relaxed);
+        })
+        .unwrap();
+        remove_handler(10).unwrap();
+        unmask(10).unwrap();
+        enable().unwrap();
+
+        sim::raise_virq(partition::my_id().unwrap(), 10);
+        assert_eq!(CALLS.load(Ordering::Relaxed), 0);
+        assert!(matches!(
+            set_handler(NUM_VIRQS as VIrq, |_| ()),
+            Err(XngError::InvalidParam)
+        ));
+    }
+
+    #[test]
+    fn handlers_are_kept_per_partition() {
+        static FIRST: AtomicU32 = AtomicU32::new(0);
+        static SECOND: AtomicU32 = AtomicU32::new(0);
+
+        sim::set_partition("First", 1);
+        set_handler(10, |_| {
+            FIRST.fetch_add(1, Ordering::Relaxed);
+        })
+        .unwrap();
+        unmask(10).unwrap();
+        enable().unwrap();
+
+        sim::set_partition("Second", 2);
+        set_handler(10, |_| {
+            SECOND.fetch_add(1, Ordering::Relaxed);
+        })
+        .unwrap();
+        unmask(10).unwrap();
+        enable().unwrap();
+
+        sim::raise_virq(2, 10);
+        assert_eq!(SECOND.load(Ordering::Relaxed), 1);
+
+        sim::set_partition("First", 1);
+        sim::raise_virq(1, 10);
+        assert_eq!(FIRST.load(Ordering::Relaxed), 1);
+        assert_eq!(SECOND.load(Ordering::Relaxed), 1);
+    }
+}
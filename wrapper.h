@@ -4,15 +4,35 @@
 #include <xBoardTypeIfc.h>
 #include <xcfTypeIfc.h>
 #include <xCommPortIfc.h>
-//#include <xHealthMonitorIfc.h>
-#include <xLegacyIfc.h>
 #include <xPartitionIfc.h>
+#include <xTypeIfc.h>
+#include <xVCpuIfc.h>
+
+// The optional interfaces are selected by the cargo features of the same name, see build.rs
+#ifdef XNG_RS_HM
+#include <xHealthMonitorIfc.h>
+#endif
+#ifdef XNG_RS_LEGACY
+#include <xLegacyIfc.h>
+#endif
+#ifdef XNG_RS_QUEUING
 #include <xQueuingPortIfc.h>
+#endif
+#ifdef XNG_RS_SAMPLING
 #include <xSamplingPortIfc.h>
+#endif
+#ifdef XNG_RS_SCHEDULE
 #include <xScheduleIfc.h>
-//#include <xTraceIfc.h>
-#include <xTypeIfc.h>
+#endif
+#ifdef XNG_RS_TRACE
+#include <xTraceIfc.h>
+#endif
+#ifdef XNG_RS_VCLOCK
 #include <xVClockIfc.h>
-#include <xVCpuIfc.h>
+#endif
+#ifdef XNG_RS_VIRQ
 #include <xVIrqCtrlIfc.h>
+#endif
+#ifdef XNG_RS_VTIMER
 #include <xVTimerIfc.h>
+#endif
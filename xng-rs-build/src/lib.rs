@@ -0,0 +1,566 @@
+//! Build script helpers for XNG partitions written with `xng-rs`
+//!
+//! [`generate_xcf`] generates typed port and partition declarations from the XNG XCF
+//! configuration. It is called from the `build.rs` of a partition, so that every partition of a
+//! workspace gets its own declarations. For every port of the partition a module is generated,
+//! containing the port's properties and a `create()` function which returns the correctly typed
+//! port, e.g. a `port::SamplingReceiver<N>` with the configured `N` and refresh period. Thus, a
+//! mismatch between XCF and code is a compile time error.
+//!
+//! The following elements of the XCF are evaluated, all others are ignored:
+//!
+//! ```xml
+//! <Partition name="Sensor" id="1">
+//!   <PortTable>
+//!     <Port name="altitude" direction="source" type="sampling"/>
+//!   </PortTable>
+//! </Partition>
+//! <Channels>
+//!   <SamplingChannel maxMessageLength="32B" refreshPeriod="100ms">
+//!     <Source partitionName="Sensor" portName="altitude"/>
+//!     <Destination partitionName="Display" portName="altitude"/>
+//!   </SamplingChannel>
+//!   <QueuingChannel maxMessageLength="64B" maxNoMessages="8">
+//!     ...
+//!   </QueuingChannel>
+//! </Channels>
+//! ```
+//!
+//! # Examples
+//!
+//! In the `build.rs` of the partition `Display`:
+//!
+//! ```no_run
+//! fn main() -> Result<(), xng_rs_build::Error> {
+//!     xng_rs_build::generate_xcf("../config/system.xcf", "Display")
+//! }
+//! ```
+//!
+//! In the partition itself:
+//!
+//! ```ignore
+//! mod xcf {
+//!     include!(concat!(env!("OUT_DIR"), "/xcf.rs"));
+//! }
+//!
+//! # fn main() -> Result<(), xng_rs::XngError> {
+//! // the XCF declares a sampling destination port named `altitude`
+//! let altitude = xcf::altitude::create()?;
+//! let mut buf = [0u8; xcf::altitude::MAX_MESSAGE_SIZE];
+//! altitude.recv(&mut buf)?;
+//! # Ok(())}
+//! ```
+
+use std::{env, fmt, fmt::Write, fs, path::Path};
+
+/// An error while generating the declarations from the XCF
+#[derive(Debug)]
+pub struct Error(String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Error(message)
+    }
+}
+
+impl From<&str> for Error {
+    fn from(message: &str) -> Self {
+        Error(message.to_owned())
+    }
+}
+
+/// Kind of a port, including the properties of its channel
+#[derive(Debug, PartialEq, Eq)]
+enum PortKind {
+    Sampling { refresh_period_us: u64 },
+    Queuing { max_messages: usize },
+}
+
+/// A port of the partition, as described in the XCF
+#[derive(Debug, PartialEq, Eq)]
+struct Port {
+    name: String,
+    source: bool,
+    max_message_size: usize,
+    kind: PortKind,
+}
+
+/// Generate `$OUT_DIR/xcf.rs` with the declarations of the partition `partition` in `xcf`
+///
+/// To be called from a build script. Cargo is told to rerun the build script when the XCF
+/// changes.
+pub fn generate_xcf(xcf: impl AsRef<Path>, partition: &str) -> Result<(), Error> {
+    let xcf = xcf.as_ref();
+    println!("cargo:rerun-if-changed={}", xcf.display());
+
+    let out_dir = env::var_os("OUT_DIR").ok_or("OUT_DIR is not set, call from a build script")?;
+    let xcf_content = fs::read_to_string(xcf)
+        .map_err(|e| format!("Unable to read XCF {}: {}", xcf.display(), e))?;
+    let code = xcf_code(&xcf_content, partition)
+        .map_err(|e| format!("Invalid XCF {}: {}", xcf.display(), e))?;
+
+    fs::write(Path::new(&out_dir).join("xcf.rs"), code)
+        .map_err(|e| format!("Couldn't write xcf.rs: {}", e))?;
+    Ok(())
+}
+
+/// Generate the declarations of the partition `partition` from the XCF content `xcf`
+///
+/// # Examples
+///
+/// ```
+/// let xcf = r#"
+///     <Configuration>
+///         <Partition name="Sensor" id="1">
+///             <PortTable><Port name="altitude" direction="source"/></PortTable>
+///         </Partition>
+///         <Channels>
+///             <SamplingChannel maxMessageLength="4B" refreshPeriod="100ms">
+///                 <Source partitionName="Sensor" portName="altitude"/>
+///             </SamplingChannel>
+///         </Channels>
+///     </Configuration>"#;
+///
+/// let code = xng_rs_build::xcf_code(xcf, "Sensor").unwrap();
+/// assert!(code.contains("pub type Port = ::xng_rs::port::SamplingSender<4>;"));
+/// ```
+pub fn xcf_code(xcf: &str, partition: &str) -> Result<String, Error> {
+    let mut code = String::from("// generated by xng-rs-build, do not edit\n");
+    generate_code(xcf, partition, &mut code)?;
+    Ok(code)
+}
+
+fn generate_code(xcf: &str, partition_name: &str, code: &mut String) -> Result<(), String> {
+    let doc = roxmltree::Document::parse(xcf).map_err(|e| e.to_string())?;
+
+    let partition = doc
+        .descendants()
+        .find(|n| n.has_tag_name("Partition") && n.attribute("name") == Some(partition_name))
+        .ok_or_else(|| format!("no partition named {}", partition_name))?;
+    let partition_id = partition.attribute("id");
+
+    // a partition does not necessarily use all of its declarations
+    writeln!(code, "/// Name of this partition").unwrap();
+    writeln!(code, "#[allow(dead_code)]").unwrap();
+    writeln!(
+        code,
+        "pub const PARTITION_NAME: &str = {:?};",
+        partition_name
+    )
+    .unwrap();
+    if let Some(id) = partition_id {
+        let id: u32 = id
+            .parse()
+            .map_err(|_| format!("invalid partition id {}", id))?;
+        writeln!(code, "/// Id of this partition").unwrap();
+        writeln!(code, "#[allow(dead_code)]").unwrap();
+        writeln!(
+            code,
+            "pub const PARTITION_ID: ::xng_rs::partition::PartitionId = {};",
+            id
+        )
+        .unwrap();
+    }
+
+    for port in partition.descendants().filter(|n| n.has_tag_name("Port")) {
+        let port = parse_port(&doc, port, partition_name, partition_id)?;
+        write_port(code, &port);
+    }
+
+    Ok(())
+}
+
+/// Parse a port of the partition, together with the properties of its channel
+fn parse_port(
+    doc: &roxmltree::Document,
+    port: roxmltree::Node,
+    partition_name: &str,
+    partition_id: Option<&str>,
+) -> Result<Port, String> {
+    let name = port.attribute("name").ok_or("port without name")?;
+    let source = match port.attribute("direction") {
+        Some("source") => true,
+        Some("destination") => false,
+        d => return Err(format!("port {} has invalid direction {:?}", name, d)),
+    };
+
+    // find the channel this port is connected to
+    let endpoint = if source { "Source" } else { "Destination" };
+    let channel = doc
+        .descendants()
+        .filter(|n| n.has_tag_name("SamplingChannel") || n.has_tag_name("QueuingChannel"))
+        .find(|c| {
+            c.children().any(|e| {
+                e.has_tag_name(endpoint)
+                    && e.attribute("portName") == Some(name)
+                    && (e.attribute("partitionName") == Some(partition_name)
+                        || (e.attribute("partitionId").is_some()
+                            && e.attribute("partitionId") == partition_id))
+            })
+        })
+        .ok_or_else(|| format!("port {} is not connected to any channel", name))?;
+
+    let channel_type = if channel.has_tag_name("SamplingChannel") {
+        "sampling"
+    } else {
+        "queuing"
+    };
+    if let Some(port_type) = port.attribute("type") {
+        if port_type != channel_type {
+            return Err(format!(
+                "port {} is declared as {} port but connected to a {} channel",
+                name, port_type, channel_type
+            ));
+        }
+    }
+
+    let max_message_size = parse_size(
+        channel
+            .attribute("maxMessageLength")
+            .ok_or_else(|| format!("channel of port {} lacks maxMessageLength", name))?,
+    )?;
+    let kind = if channel.has_tag_name("SamplingChannel") {
+        let refresh_period = channel
+            .attribute("refreshPeriod")
+            .ok_or_else(|| format!("channel of port {} lacks refreshPeriod", name))?;
+        PortKind::Sampling {
+            refresh_period_us: parse_time_us(refresh_period)?,
+        }
+    } else {
+        let max_messages = channel
+            .attribute("maxNoMessages")
+            .ok_or_else(|| format!("channel of port {} lacks maxNoMessages", name))?;
+        PortKind::Queuing {
+            max_messages: max_messages
+                .parse()
+                .map_err(|_| format!("invalid maxNoMessages {}", max_messages))?,
+        }
+    };
+
+    Ok(Port {
+        name: name.to_owned(),
+        source,
+        max_message_size,
+        kind,
+    })
+}
+
+/// Emit a module describing one port
+fn write_port(code: &mut String, port: &Port) {
+    let direction = if port.source { "source" } else { "destination" };
+    let (kind, ty, constructor) = match port.kind {
+        PortKind::Sampling { .. } if port.source => (
+            "sampling",
+            format!("::xng_rs::port::SamplingSender<{}>", port.max_message_size),
+            format!("Port::new(::xng_rs::cstr!({:?}))", port.name),
+        ),
+        PortKind::Sampling { .. } => (
+            "sampling",
+            format!(
+                "::xng_rs::port::SamplingReceiver<{}>",
+                port.max_message_size
+            ),
+            format!(
+                "Port::new(::xng_rs::cstr!({:?}), REFRESH_PERIOD)",
+                port.name
+            ),
+        ),
+        PortKind::Queuing { max_messages } => (
+            "queuing",
+            format!(
+                "::xng_rs::port::Queuing{}<{}, {}>",
+                if port.source { "Sender" } else { "Receiver" },
+                port.max_message_size,
+                max_messages
+            ),
+            format!("Port::new(::xng_rs::cstr!({:?}))", port.name),
+        ),
+    };
+
+    writeln!(
+        code,
+        "\n/// The {} {} port `{}`",
+        kind, direction, port.name
+    )
+    .unwrap();
+    writeln!(code, "#[allow(dead_code)]").unwrap();
+    writeln!(code, "pub mod {} {{", module_name(&port.name)).unwrap();
+    writeln!(code, "    #[allow(unused_imports)]").unwrap();
+    writeln!(code, "    use ::xng_rs::{{time::Duration, XngError}};").unwrap();
+    writeln!(code, "    /// Name of the port").unwrap();
+    writeln!(code, "    pub const NAME: &str = {:?};", port.name).unwrap();
+    writeln!(code, "    /// Maximum size of a message in bytes").unwrap();
+    writeln!(
+        code,
+        "    pub const MAX_MESSAGE_SIZE: usize = {};",
+        port.max_message_size
+    )
+    .unwrap();
+    match port.kind {
+        PortKind::Sampling { refresh_period_us } => {
+            writeln!(code, "    /// Refresh period of the channel").unwrap();
+            writeln!(
+                code,
+                "    pub const REFRESH_PERIOD: Duration = Duration::from_micros({});",
+                refresh_period_us
+            )
+            .unwrap();
+        }
+        PortKind::Queuing { max_messages } => {
+            writeln!(code, "    /// Maximum number of messages in the queue").unwrap();
+            writeln!(
+                code,
+                "    pub const MAX_MESSAGES: usize = {};",
+                max_messages
+            )
+            .unwrap();
+        }
+    }
+    writeln!(code, "    /// Type of this end of the port").unwrap();
+    writeln!(code, "    pub type Port = {};", ty).unwrap();
+    writeln!(code, "    /// Create this end of the port").unwrap();
+    writeln!(code, "    pub fn create() -> Result<Port, XngError> {{").unwrap();
+    writeln!(code, "        {}", constructor).unwrap();
+    writeln!(code, "    }}").unwrap();
+    writeln!(code, "}}").unwrap();
+}
+
+/// Keywords which are escaped as raw identifiers when used as module name
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let",
+    "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return",
+    "static", "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use",
+    "virtual", "where", "while", "yield",
+];
+
+/// Keywords which can not be raw identifiers, they get a trailing underscore instead
+const PATH_KEYWORDS: &[&str] = &["crate", "self", "super"];
+
+/// Turn a port name into a valid snake case module name
+fn module_name(port_name: &str) -> String {
+    let mut name = String::new();
+    let mut prev_lower = false;
+    for c in port_name.chars() {
+        if c.is_ascii_uppercase() {
+            if prev_lower {
+                name.push('_');
+            }
+            name.push(c.to_ascii_lowercase());
+            prev_lower = false;
+        } else if c.is_ascii_alphanumeric() {
+            name.push(c);
+            prev_lower = true;
+        } else {
+            name.push('_');
+            prev_lower = false;
+        }
+    }
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, '_');
+    }
+    // neither `_` nor an empty name is a valid identifier
+    if name.chars().all(|c| c == '_') {
+        name.push_str("port");
+    }
+
+    if KEYWORDS.contains(&name.as_str()) {
+        name.insert_str(0, "r#");
+    } else if PATH_KEYWORDS.contains(&name.as_str()) {
+        name.push('_');
+    }
+    name
+}
+
+/// Parse a size like `32B` or `1KB` into bytes
+fn parse_size(size: &str) -> Result<usize, String> {
+    let (number, factor) = split_unit(size, &[("KB", 1024), ("B", 1), ("", 1)]);
+    number
+        .parse::<usize>()
+        .map(|n| n * factor as usize)
+        .map_err(|_| format!("invalid size {}", size))
+}
+
+/// Parse a time like `100ms` or `20us` into microseconds
+fn parse_time_us(time: &str) -> Result<u64, String> {
+    let (number, factor) = split_unit(time, &[("us", 1), ("ms", 1_000), ("s", 1_000_000)]);
+    number
+        .parse::<u64>()
+        .map(|n| n * factor)
+        .map_err(|_| format!("invalid time {}", time))
+}
+
+fn split_unit<'a>(value: &'a str, units: &[(&str, u64)]) -> (&'a str, u64) {
+    let value = value.trim();
+    units
+        .iter()
+        .find_map(|(unit, factor)| value.strip_suffix(unit).map(|n| (n.trim(), *factor)))
+        .unwrap_or((value, 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parse the ports of the partition `partition` in `xcf`
+    fn ports(xcf: &str, partition: &str) -> Result<Vec<Port>, String> {
+        let doc = roxmltree::Document::parse(xcf).map_err(|e| e.to_string())?;
+        let partition = doc
+            .descendants()
+            .find(|n| n.has_tag_name("Partition") && n.attribute("name") == Some(partition))
+            .ok_or("no such partition")?;
+        let partition_id = partition.attribute("id");
+        partition
+            .descendants()
+            .filter(|n| n.has_tag_name("Port"))
+            .map(|port| {
+                parse_port(
+                    &doc,
+                    port,
+                    partition.attribute("name").unwrap(),
+                    partition_id,
+                )
+            })
+            .collect()
+    }
+
+    const XCF: &str = r#"
+        <Configuration>
+            <Partition name="Sensor" id="1">
+                <PortTable>
+                    <Port name="altitude" direction="source" type="sampling"/>
+                    <Port name="commands" direction="destination" type="queuing"/>
+                </PortTable>
+            </Partition>
+            <Partition name="Display" id="2">
+                <PortTable>
+                    <Port name="altitudeIn" direction="destination"/>
+                    <Port name="commands" direction="source"/>
+                </PortTable>
+            </Partition>
+            <Channels>
+                <SamplingChannel maxMessageLength="1KB" refreshPeriod="100ms">
+                    <Source partitionName="Sensor" portName="altitude"/>
+                    <Destination partitionId="2" portName="altitudeIn"/>
+                </SamplingChannel>
+                <QueuingChannel maxMessageLength="64B" maxNoMessages="8">
+                    <Source partitionName="Display" portName="commands"/>
+                    <Destination partitionName="Sensor" portName="commands"/>
+                </QueuingChannel>
+            </Channels>
+        </Configuration>"#;
+
+    #[test]
+    fn ports_of_partition() {
+        assert_eq!(
+            ports(XCF, "Sensor").unwrap(),
+            [
+                Port {
+                    name: "altitude".into(),
+                    source: true,
+                    max_message_size: 1024,
+                    kind: PortKind::Sampling {
+                        refresh_period_us: 100_000
+                    },
+                },
+                Port {
+                    name: "commands".into(),
+                    source: false,
+                    max_message_size: 64,
+                    kind: PortKind::Queuing { max_messages: 8 },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn channel_endpoint_by_partition_id() {
+        let ports = ports(XCF, "Display").unwrap();
+        assert_eq!(ports[0].name, "altitudeIn");
+        assert!(!ports[0].source);
+        assert_eq!(ports[1].kind, PortKind::Queuing { max_messages: 8 });
+    }
+
+    #[test]
+    fn generated_code() {
+        let code = xcf_code(XCF, "Display").unwrap();
+        assert!(code.contains("pub const PARTITION_NAME: &str = \"Display\";"));
+        assert!(code.contains("pub const PARTITION_ID: ::xng_rs::partition::PartitionId = 2;"));
+        assert!(code.contains("pub mod altitude_in {"));
+        assert!(code.contains("pub type Port = ::xng_rs::port::SamplingReceiver<1024>;"));
+        assert!(code.contains("Duration::from_micros(100000)"));
+        assert!(code.contains("pub type Port = ::xng_rs::port::QueuingSender<64, 8>;"));
+    }
+
+    #[test]
+    fn unknown_partition() {
+        assert!(xcf_code(XCF, "Actuator").is_err());
+    }
+
+    #[test]
+    fn sampling_channel_without_refresh_period() {
+        let xcf = XCF.replace(r#" refreshPeriod="100ms""#, "");
+        let error = ports(&xcf, "Sensor").unwrap_err();
+        assert!(error.contains("lacks refreshPeriod"), "{}", error);
+    }
+
+    #[test]
+    fn mismatching_port_type() {
+        let xcf = XCF.replace(r#"type="queuing""#, r#"type="sampling""#);
+        let error = ports(&xcf, "Sensor").unwrap_err();
+        assert!(
+            error.contains("connected to a queuing channel"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn unconnected_port() {
+        let xcf = XCF.replace(r#"portName="altitude""#, r#"portName="speed""#);
+        let error = ports(&xcf, "Sensor").unwrap_err();
+        assert!(error.contains("not connected"), "{}", error);
+    }
+
+    #[test]
+    fn invalid_direction() {
+        let xcf = XCF.replace(r#"direction="source" type"#, r#"direction="both" type"#);
+        assert!(ports(&xcf, "Sensor").is_err());
+    }
+
+    #[test]
+    fn module_names() {
+        assert_eq!(module_name("altitude"), "altitude");
+        assert_eq!(module_name("AltitudeIn"), "altitude_in");
+        assert_eq!(module_name("altitude-in.1"), "altitude_in_1");
+        assert_eq!(module_name("1st"), "_1st");
+        assert_eq!(module_name("type"), "r#type");
+        assert_eq!(module_name("Self"), "self_");
+        assert_eq!(module_name("super"), "super_");
+        assert_eq!(module_name("_"), "_port");
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("32B"), Ok(32));
+        assert_eq!(parse_size("2KB"), Ok(2048));
+        assert_eq!(parse_size(" 16 "), Ok(16));
+        assert!(parse_size("many").is_err());
+    }
+
+    #[test]
+    fn times() {
+        assert_eq!(parse_time_us("20us"), Ok(20));
+        assert_eq!(parse_time_us("100ms"), Ok(100_000));
+        assert_eq!(parse_time_us("2s"), Ok(2_000_000));
+        assert!(parse_time_us("soon").is_err());
+    }
+}